@@ -13,8 +13,8 @@ impl App {
 }
 
 impl eframe::App for App {
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        self.easy_21.show(ctx);
+    fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
+        self.easy_21.show(ctx, frame.storage_mut());
 
         ctx.request_repaint();
     }
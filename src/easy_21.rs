@@ -2,8 +2,31 @@ use egui::Grid;
 use plotters::prelude::*;
 use rand::{
     distributions::{uniform::Uniform, Distribution},
-    Rng,
+    rngs::StdRng,
+    Rng, SeedableRng,
 };
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::{collections::VecDeque, marker::PhantomData, ops::RangeInclusive};
+
+use crate::env::{Environment, Sample, Tabular};
+use batch::{BatchMethod, BatchState};
+use dyna::{DynaMethod, DynaState};
+use exploration::{Counted, Exploration, Schedule, Strategy};
+use features::{FeatureExtractor, Features, StateFeatureExtractor};
+use policy_gradient::{PolicyGradientMethod, PolicyGradientState};
+use step_size::StepSize;
+
+pub mod baird;
+pub mod batch;
+pub mod dp;
+pub mod dyna;
+pub mod experiment;
+pub mod exploration;
+pub mod features;
+pub mod persistence;
+pub mod policy_gradient;
+pub mod policy_search;
+pub mod step_size;
 
 #[derive(Copy, Clone, Debug, PartialEq)]
 enum Algorithm {
@@ -11,28 +34,519 @@ enum Algorithm {
     MonteCarloControl,
     TDLambdaPrediction,
     TDLambdaControl,
+    QLearning,
+    WatkinsQLambda,
+    ExpectedSarsa,
+    DoubleQLearning,
+    NStepSarsa,
+    NStepTreeBackup,
     ApproxTDLambdaControl,
+    TrueOnlineSarsaLambda,
+    OffPolicyMCPrediction(Sampling),
+    OffPolicyMCControl(Sampling),
+    GradientMonteCarloPrediction,
+    ApproxTDLambdaPrediction,
+    OffPolicyTDLambdaPrediction,
+    GTD2Prediction,
+    TDCPrediction,
+    LSTDLambdaPrediction,
+    LSPIControl,
+    Reinforce,
+    OneStepActorCritic,
+    ActorCriticLambda,
+    DynaQ,
+    DynaQPlus,
 }
 
 impl Algorithm {
-    fn initial_state(&self) -> Box<dyn Easy21State> {
+    fn initial_state(&self, h: Hyperparameters) -> Box<dyn Easy21State> {
         match self {
-            Self::MonteCarloPrediction => Box::new(MCState::init(example_policy)),
-            Self::MonteCarloControl => Box::new(MCControlState::init()),
-            Self::TDLambdaPrediction => Box::new(TDState::init(example_policy)),
-            Self::TDLambdaControl => Box::new(TDControlState::init()),
-            Self::ApproxTDLambdaControl => Box::new(ApproxState::init()),
+            Self::MonteCarloPrediction => Box::new(MCState::init(h, example_policy)),
+            Self::MonteCarloControl => Box::new(MCControlState::init(h)),
+            Self::TDLambdaPrediction => Box::new(TDState::init(h, example_policy)),
+            Self::TDLambdaControl => Box::new(TDControlState::init(h)),
+            Self::QLearning => Box::new(TDControlState {
+                method: TDControlMethod::QLearning,
+                ..TDControlState::init(h)
+            }),
+            Self::WatkinsQLambda => Box::new(TDControlState {
+                method: TDControlMethod::WatkinsQLambda,
+                ..TDControlState::init(h)
+            }),
+            Self::ExpectedSarsa => Box::new(TDControlState {
+                method: TDControlMethod::ExpectedSarsa,
+                ..TDControlState::init(h)
+            }),
+            Self::DoubleQLearning => Box::new(DoubleQState::init(h)),
+            Self::NStepSarsa => Box::new(TDControlState {
+                method: TDControlMethod::NStepSarsa,
+                ..TDControlState::init(h)
+            }),
+            Self::NStepTreeBackup => Box::new(TDControlState {
+                method: TDControlMethod::NStepTreeBackup,
+                ..TDControlState::init(h)
+            }),
+            Self::ApproxTDLambdaControl => Box::new(ApproxState::init(h, h.features)),
+            Self::TrueOnlineSarsaLambda => Box::new(ApproxState {
+                method: LinearControlMethod::TrueOnlineSarsa,
+                ..ApproxState::init(h, h.features)
+            }),
+            Self::OffPolicyMCPrediction(sampling) => {
+                Box::new(OffPolicyMCState::init(h, *sampling, Some(example_policy)))
+            }
+            Self::OffPolicyMCControl(sampling) => {
+                Box::new(OffPolicyMCState::init(h, *sampling, None))
+            }
+            Self::GradientMonteCarloPrediction => Box::new(ApproxPredictionState {
+                method: LinearPredictionMethod::GradientMonteCarlo,
+                ..ApproxPredictionState::init(h, h.features, example_policy)
+            }),
+            Self::ApproxTDLambdaPrediction => {
+                Box::new(ApproxPredictionState::init(h, h.features, example_policy))
+            }
+            Self::OffPolicyTDLambdaPrediction => Box::new(ApproxPredictionState {
+                method: LinearPredictionMethod::OffPolicyTDLambda,
+                ..ApproxPredictionState::init(h, h.features, example_policy)
+            }),
+            Self::GTD2Prediction => Box::new(ApproxPredictionState {
+                method: LinearPredictionMethod::GTD2,
+                ..ApproxPredictionState::init(h, h.features, example_policy)
+            }),
+            Self::TDCPrediction => Box::new(ApproxPredictionState {
+                method: LinearPredictionMethod::TDC,
+                ..ApproxPredictionState::init(h, h.features, example_policy)
+            }),
+            Self::LSTDLambdaPrediction => {
+                Box::new(BatchState::init(h, BatchMethod::LSTDLambdaPrediction))
+            }
+            Self::LSPIControl => Box::new(BatchState::init(h, BatchMethod::LSPIControl)),
+            Self::Reinforce => Box::new(PolicyGradientState::init(h, h.features)),
+            Self::OneStepActorCritic => Box::new(PolicyGradientState {
+                method: PolicyGradientMethod::OneStepActorCritic,
+                ..PolicyGradientState::init(h, h.features)
+            }),
+            Self::ActorCriticLambda => Box::new(PolicyGradientState {
+                method: PolicyGradientMethod::ActorCriticLambda,
+                ..PolicyGradientState::init(h, h.features)
+            }),
+            Self::DynaQ => Box::new(DynaState::init(h)),
+            Self::DynaQPlus => Box::new(DynaState {
+                method: DynaMethod::DynaQPlus,
+                ..DynaState::init(h)
+            }),
+        }
+
+    }
+
+    /// Whether the algorithm learns linear values and reads
+    /// [`Hyperparameters::features`].
+    fn is_linear(&self) -> bool {
+        matches!(
+            self,
+            Self::ApproxTDLambdaControl
+                | Self::TrueOnlineSarsaLambda
+                | Self::GradientMonteCarloPrediction
+                | Self::ApproxTDLambdaPrediction
+                | Self::OffPolicyTDLambdaPrediction
+                | Self::GTD2Prediction
+                | Self::TDCPrediction
+                | Self::Reinforce
+                | Self::OneStepActorCritic
+                | Self::ActorCriticLambda
+        )
+    }
+
+    /// Whether the algorithm reads [`Hyperparameters::trace`].
+    fn has_trace_variants(&self) -> bool {
+        matches!(
+            self,
+            Self::TDLambdaPrediction
+                | Self::TDLambdaControl
+                | Self::WatkinsQLambda
+                | Self::ApproxTDLambdaControl
+                | Self::ApproxTDLambdaPrediction
+        )
+    }
+
+    fn hyperparameters(&self) -> Hyperparameters {
+        let h = Hyperparameters::default();
+        match self {
+            Self::MonteCarloPrediction => Hyperparameters {
+                step_size: StepSize::SampleAverage,
+                ..h
+            },
+            Self::MonteCarloControl => Hyperparameters {
+                step_size: StepSize::SampleAverage,
+                exploration: Strategy::EpsilonGreedy(Schedule::Harmonic {
+                    offset: 10.0,
+                    visits: 100_000.0,
+                }),
+                ..h
+            },
+            Self::TDLambdaPrediction => Hyperparameters { lambda: 0.5, ..h },
+            Self::TDLambdaControl
+            | Self::QLearning
+            | Self::WatkinsQLambda
+            | Self::ExpectedSarsa
+            | Self::DoubleQLearning
+            | Self::NStepSarsa
+            | Self::NStepTreeBackup
+            | Self::DynaQ
+            | Self::DynaQPlus => h,
+            Self::ApproxTDLambdaControl | Self::TrueOnlineSarsaLambda => Hyperparameters {
+                lambda: 0.1,
+                step_size: StepSize::Constant(0.0001),
+                exploration: Strategy::EpsilonGreedy(Schedule::Constant(0.05)),
+                ..h
+            },
+            Self::OffPolicyMCPrediction(_) | Self::OffPolicyMCControl(_) => Hyperparameters {
+                exploration: Strategy::EpsilonGreedy(Schedule::Constant(0.3)),
+                ..h
+            },
+            Self::GradientMonteCarloPrediction => Hyperparameters {
+                step_size: StepSize::Constant(0.001),
+                ..h
+            },
+            Self::ApproxTDLambdaPrediction => Hyperparameters {
+                lambda: 0.5,
+                step_size: StepSize::Constant(0.001),
+                ..h
+            },
+            Self::OffPolicyTDLambdaPrediction | Self::GTD2Prediction | Self::TDCPrediction => {
+                Hyperparameters {
+                    lambda: 0.5,
+                    step_size: StepSize::Constant(0.001),
+                    exploration: Strategy::EpsilonGreedy(Schedule::Constant(0.3)),
+                    ..h
+                }
+            }
+            Self::LSTDLambdaPrediction => Hyperparameters { lambda: 0.5, ..h },
+            Self::LSPIControl => Hyperparameters {
+                exploration: Strategy::EpsilonGreedy(Schedule::Constant(0.3)),
+                ..h
+            },
+            Self::Reinforce => Hyperparameters {
+                step_size: StepSize::Constant(0.01),
+                ..h
+            },
+            Self::OneStepActorCritic | Self::ActorCriticLambda => Hyperparameters {
+                lambda: 0.5,
+                step_size: StepSize::Constant(0.01),
+                ..h
+            },
+        }
+    }
+}
+
+/// Settings of the learning algorithms. Each algorithm reads only some of the fields.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Hyperparameters {
+    /// Decay of the eligibility traces.
+    pub lambda: f64,
+    /// How eligibility traces grow on visits. True online SARSA(λ) always uses dutch
+    /// traces.
+    pub trace: Trace,
+    /// Step size of the value updates. Off-policy Monte Carlo averages with its
+    /// importance sampling weights instead.
+    pub step_size: StepSize,
+    /// How control methods pick their actions, and how off-policy methods pick the
+    /// actions of the behaviour policy. Linear methods and off-policy Monte Carlo keep
+    /// no visit counts, so only constant schedules make sense for them.
+    pub exploration: Strategy,
+    /// Step size of the secondary weights of gradient-TD methods, relative to
+    /// `step_size`.
+    pub secondary_step_size: f64,
+    /// Step size of the state values of policy-gradient methods, where `step_size` is
+    /// that of the policy.
+    pub critic_step_size: StepSize,
+    /// Whether REINFORCE subtracts the state values from the returns.
+    pub baseline: bool,
+    /// Simulated steps of Dyna-Q after every real step.
+    pub planning_steps: usize,
+    /// Factor κ of the bonus κ√τ that Dyna-Q+ adds to the simulated reward of a
+    /// state-action pair last tried τ real steps ago.
+    pub exploration_bonus: f64,
+    /// Number of rewards in the returns of n-step methods.
+    pub n: usize,
+    /// Features of linear methods. Changes take effect on the next restart, since they
+    /// change the number of weights.
+    pub features: Features,
+}
+
+impl Default for Hyperparameters {
+    fn default() -> Self {
+        Self {
+            lambda: 0.6,
+            trace: Trace::Accumulating,
+            step_size: StepSize::default(),
+            exploration: Strategy::default(),
+            secondary_step_size: 10.0,
+            critic_step_size: StepSize::Constant(0.01),
+            baseline: true,
+            planning_steps: 10,
+            exploration_bonus: 0.0001,
+            n: 4,
+            features: Features::default(),
+        }
+    }
+}
+
+impl Hyperparameters {
+    /// Shows sliders for the settings that `algorithm` reads, and returns whether any of
+    /// them changed.
+    fn ui(&mut self, ui: &mut egui::Ui, algorithm: Algorithm) -> bool {
+        use Algorithm::*;
+        let traces = matches!(
+            algorithm,
+            TDLambdaPrediction
+                | TDLambdaControl
+                | WatkinsQLambda
+                | ApproxTDLambdaControl
+                | TrueOnlineSarsaLambda
+                | ApproxTDLambdaPrediction
+                | OffPolicyTDLambdaPrediction
+                | GTD2Prediction
+                | TDCPrediction
+                | LSTDLambdaPrediction
+                | ActorCriticLambda
+        );
+        let n_step = matches!(algorithm, NStepSarsa | NStepTreeBackup);
+        let planning = matches!(algorithm, DynaQ | DynaQPlus);
+        let gradient_td = matches!(algorithm, GTD2Prediction | TDCPrediction);
+        let policy_gradient = matches!(
+            algorithm,
+            Reinforce | OneStepActorCritic | ActorCriticLambda
+        );
+        let linear = algorithm.is_linear();
+        // Whether the learner counts visits, otherwise its `Counted::count` is always 0.
+        let counted = !linear
+            && !matches!(
+                algorithm,
+                OffPolicyMCPrediction(_) | OffPolicyMCControl(_) | LSPIControl
+            );
+        let step_size = !matches!(
+            algorithm,
+            OffPolicyMCPrediction(_) | OffPolicyMCControl(_) | LSTDLambdaPrediction | LSPIControl
+        );
+        let exploration = !matches!(
+            algorithm,
+            MonteCarloPrediction
+                | TDLambdaPrediction
+                | GradientMonteCarloPrediction
+                | ApproxTDLambdaPrediction
+                | LSTDLambdaPrediction
+                | Reinforce
+                | OneStepActorCritic
+                | ActorCriticLambda
+        );
+
+        let mut changed = false;
+        Grid::new("hyperparameters").num_columns(2).show(ui, |ui| {
+            if n_step {
+                ui.label("n:");
+                changed |= ui.add(egui::Slider::new(&mut self.n, 1..=50)).changed();
+                ui.end_row();
+            }
+            if planning {
+                ui.label("Planning steps:");
+                changed |= ui
+                    .add(egui::Slider::new(&mut self.planning_steps, 0..=100))
+                    .changed();
+                ui.end_row();
+            }
+            if algorithm == DynaQPlus {
+                ui.label("κ:")
+                    .on_hover_text("Bonus κ√τ for pairs last tried τ steps ago.");
+                changed |= ui
+                    .add(
+                        egui::Slider::new(&mut self.exploration_bonus, 1e-6..=0.1)
+                            .logarithmic(true),
+                    )
+                    .changed();
+                ui.end_row();
+            }
+            if traces {
+                ui.label("λ:");
+                changed |= ui
+                    .add(egui::Slider::new(&mut self.lambda, 0.0..=1.0))
+                    .changed();
+                ui.end_row();
+            }
+            if algorithm.has_trace_variants() {
+                ui.label("Traces:");
+                ui.horizontal(|ui| {
+                    for trace in [Trace::Accumulating, Trace::Replacing, Trace::Dutch] {
+                        changed |= ui
+                            .selectable_value(&mut self.trace, trace, format!("{:?}", trace))
+                            .changed();
+                    }
+                });
+                ui.end_row();
+            }
+            if step_size {
+                let name = if policy_gradient {
+                    "Policy step size"
+                } else {
+                    "Step size"
+                };
+                changed |= self.step_size.ui(ui, name);
+            }
+            if policy_gradient {
+                changed |= self.critic_step_size.ui(ui, "Value step size");
+            }
+            if algorithm == Reinforce {
+                ui.label("Baseline:");
+                changed |= ui.checkbox(&mut self.baseline, "").changed();
+                ui.end_row();
+            }
+            if gradient_td {
+                ui.label("β / α:").on_hover_text(
+                    "Step size of the secondary weights, relative to the step size.",
+                );
+                changed |= ui
+                    .add(
+                        egui::Slider::new(&mut self.secondary_step_size, 0.01..=100.0)
+                            .logarithmic(true),
+                    )
+                    .changed();
+                ui.end_row();
+            }
+            if exploration {
+                changed |= self.exploration.ui(ui, counted);
+            }
+            if linear {
+                changed |= self.features.ui(ui);
+            }
+        });
+        changed
+    }
+}
+
+enum FileAction {
+    Save(persistence::Format),
+    Load,
+}
+
+/// How the eligibility trace of a visited state or state-action pair grows.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum Trace {
+    /// Adds one on every visit.
+    #[default]
+    Accumulating,
+    /// Resets to one on every visit.
+    Replacing,
+    /// In between: `(1 - α) e + 1`, where α is the step size.
+    Dutch,
+}
+
+impl Trace {
+    /// The trace of a visited entry, given its decayed trace `e` and step size `alpha`.
+    fn visit(&self, e: f64, alpha: f64) -> f64 {
+        match self {
+            Trace::Accumulating => e + 1.0,
+            Trace::Replacing => 1.0,
+            Trace::Dutch => (1.0 - alpha) * e + 1.0,
+        }
+    }
+
+    /// Decays linear traces by `lambda` and adds the features `x` of the visited pair.
+    /// Replacing traces reset the components of active features to their value.
+    fn update_linear(&self, traces: &mut Traces, x: &SparseVector, lambda: f64, alpha: f64) {
+        // Read before decaying, as the dutch update is defined on the old traces.
+        let dot: f64 = x.entries.iter().map(|(i, x)| x * traces.get(*i)).sum();
+        let coefficient = 1.0 - alpha * lambda * dot;
+        traces.decay(lambda);
+        for &(i, x) in &x.entries {
+            match self {
+                Trace::Accumulating => traces.update(i, |e| e + x),
+                Trace::Replacing => traces.update(i, |_| x),
+                Trace::Dutch => traces.update(i, |e| e + coefficient * x),
+            }
+        }
+    }
+}
+
+/// Traces below this are dropped. Their entries would move by less than a millionth of
+/// the step of the visited entry.
+const NEGLIGIBLE_TRACE: f64 = 1e-6;
+
+/// The non-zero eligibility traces of an episode, keyed by the index of their entry in a
+/// table or weight vector. Only entries visited in the episode have a trace, and traces
+/// that decay below [`NEGLIGIBLE_TRACE`] are dropped, so that a step costs time in the
+/// number of recent visits rather than in the size of the table.
+#[derive(Clone, Debug, Default)]
+pub struct Traces {
+    entries: Vec<(usize, f64)>,
+}
+
+impl Traces {
+    /// Drops all traces, at the start of an episode.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// Multiplies all traces by `factor` and drops the negligible ones.
+    pub fn decay(&mut self, factor: f64) {
+        self.entries.retain_mut(|(_, e)| {
+            *e *= factor;
+            e.abs() >= NEGLIGIBLE_TRACE
+        });
+    }
+
+    pub fn get(&self, index: usize) -> f64 {
+        self.entries
+            .iter()
+            .find(|(i, _)| *i == index)
+            .map_or(0.0, |(_, e)| *e)
+    }
+
+    /// Sets the trace of entry `index` to `f` of its current trace.
+    pub fn update<F: FnOnce(f64) -> f64>(&mut self, index: usize, f: F) {
+        match self.entries.iter_mut().find(|(i, _)| *i == index) {
+            Some((_, e)) => *e = f(*e),
+            None => self.entries.push((index, f(0.0))),
         }
+    }
 
+    /// Index and trace of every entry with a trace.
+    pub fn iter(&self) -> impl Iterator<Item = &(usize, f64)> {
+        self.entries.iter()
     }
 }
 
+/// A learner together with the environment it learns in.
+struct Learner {
+    env: Easy21Env,
+    state: Box<dyn Easy21State>,
+}
+
 pub struct Easy21 {
-    rng: rand::prelude::ThreadRng,
+    /// Settings for the next restart. Changes also apply to the running algorithm.
+    hyperparameters: Hyperparameters,
+    /// Seed of `rng`. Restarting with the same seed and settings repeats a run exactly.
+    seed: u64,
+    rng: StdRng,
     updates_per_frame: i32,
     algorithm: Algorithm,
-    chart: egui_plotter::Chart<Box<dyn Easy21State>>,
+    chart: egui_plotter::Chart<Learner>,
     rms: Vec<(f64, f64)>,
+    /// Label of the run that `rms` belongs to.
+    rms_label: String,
+    /// Error curves of the last finished run of each algorithm, for comparison.
+    previous_rms: Vec<(String, Vec<(f64, f64)>)>,
+    reference: dp::Reference,
+    sweep: experiment::LambdaSweep,
+    show_sweep: bool,
+    baird: baird::Demo,
+    show_baird: bool,
+    search: policy_search::Demo,
+    show_search: bool,
+    /// Where snapshots are saved to and loaded from. The extension picks the format.
+    #[cfg(not(target_arch = "wasm32"))]
+    path: String,
+    /// Outcome of the last save or load.
+    file_status: String,
 }
 
 impl Default for Easy21 {
@@ -44,14 +558,25 @@ impl Default for Easy21 {
 impl Easy21 {
     pub fn new() -> Self {
         let algorithm = Algorithm::MonteCarloControl;
-        let state = algorithm.initial_state();
-        let chart = egui_plotter::Chart::new(state)
+        let hyperparameters = algorithm.hyperparameters();
+        let env = Easy21Env::default();
+        let learner = Learner {
+            env,
+            state: algorithm.initial_state(hyperparameters),
+        };
+        let chart = egui_plotter::Chart::new(learner)
             .mouse(egui_plotter::MouseConfig::enabled())
             .pitch(0.2)
             .yaw(-0.5)
-            .builder_cb(Box::new(|area, transform, state| {
+            .builder_cb(Box::new(|area, transform, learner| {
+                let rules = &learner.env.rules;
+                let state = &learner.state;
                 let mut chart = ChartBuilder::on(area)
-                    .build_cartesian_3d(1.0..21.0, -1.0..1.0, 1.0..10.0)
+                    .build_cartesian_3d(
+                        rules.min_sum as f64..rules.max_sum as f64,
+                        -1.0..1.0,
+                        rules.min_card as f64..rules.max_card as f64,
+                    )
                     .unwrap();
 
                 chart.with_projection(|mut p| {
@@ -69,8 +594,10 @@ impl Easy21 {
                     .draw()
                     .unwrap();
 
-                let states: Vec<_> = (1..21)
-                    .flat_map(|player| (1..10).map(move |dealer| State { player, dealer }))
+                let states: Vec<_> = (rules.min_sum..rules.max_sum)
+                    .flat_map(|player| {
+                        (rules.min_card..rules.max_card).map(move |dealer| State { player, dealer })
+                    })
                     .collect();
 
                 chart
@@ -120,25 +647,48 @@ impl Easy21 {
                     .unwrap();
             }));
 
+        let seed = rand::random::<u32>() as u64;
         Self {
-            rng: rand::thread_rng(),
+            hyperparameters,
+            seed,
+            rng: StdRng::seed_from_u64(seed),
             updates_per_frame: 50,
             algorithm,
             chart,
             rms: vec![],
+            rms_label: run_label(algorithm, hyperparameters),
+            previous_rms: vec![],
+            reference: dp::Reference::new(&env.rules),
+            sweep: experiment::LambdaSweep::default(),
+            show_sweep: false,
+            baird: baird::Demo::default(),
+            show_baird: false,
+            search: policy_search::Demo::default(),
+            show_search: false,
+            #[cfg(not(target_arch = "wasm32"))]
+            path: "easy21.json".to_owned(),
+            file_status: String::new(),
         }
     }
 
-    pub fn show(&mut self, ctx: &egui::Context) {
-        let state = self.chart.get_data_mut();
+    /// Shows the windows and trains for a frame. On the web, snapshots are saved to
+    /// `storage`.
+    pub fn show(
+        &mut self,
+        ctx: &egui::Context,
+        storage: Option<&mut (dyn eframe::Storage + 'static)>,
+    ) {
+        let Learner { env, state } = self.chart.get_data_mut();
         let start_time = web_time::Instant::now();
         for _ in 0..self.updates_per_frame {
-            state.update(&mut self.rng);
+            state.update(env, &mut self.rng);
         }
         let elapsed = start_time.elapsed();
         let target_time_per_frame = 1_000_000.0 / 80.0;
         self.updates_per_frame = (self.updates_per_frame as f64 * target_time_per_frame / elapsed.as_micros() as f64).round() as i32;
 
+        let mut restart = false;
+        let mut file_action = None;
         egui::Window::new("Easy21").show(ctx, |ui| {
             egui::ComboBox::from_label("Algorithm")
                 .selected_text(format!("{:?}", self.algorithm))
@@ -150,12 +700,35 @@ impl Easy21 {
                         Algorithm::MonteCarloPrediction,
                         Algorithm::TDLambdaPrediction,
                         Algorithm::TDLambdaControl,
+                        Algorithm::QLearning,
+                        Algorithm::WatkinsQLambda,
+                        Algorithm::ExpectedSarsa,
+                        Algorithm::DoubleQLearning,
+                        Algorithm::NStepSarsa,
+                        Algorithm::NStepTreeBackup,
                         Algorithm::ApproxTDLambdaControl,
+                        Algorithm::TrueOnlineSarsaLambda,
+                        Algorithm::OffPolicyMCPrediction(Sampling::Ordinary),
+                        Algorithm::OffPolicyMCPrediction(Sampling::Weighted),
+                        Algorithm::OffPolicyMCControl(Sampling::Ordinary),
+                        Algorithm::OffPolicyMCControl(Sampling::Weighted),
+                        Algorithm::GradientMonteCarloPrediction,
+                        Algorithm::ApproxTDLambdaPrediction,
+                        Algorithm::OffPolicyTDLambdaPrediction,
+                        Algorithm::GTD2Prediction,
+                        Algorithm::TDCPrediction,
+                        Algorithm::LSTDLambdaPrediction,
+                        Algorithm::LSPIControl,
+                        Algorithm::Reinforce,
+                        Algorithm::OneStepActorCritic,
+                        Algorithm::ActorCriticLambda,
+                        Algorithm::DynaQ,
+                        Algorithm::DynaQPlus,
                     ];
                     for algo in algos {
-                        if ui.selectable_value(&mut self.algorithm, algo, format!("{:?}", &algo)).clicked() {
-                            self.rms = vec![];
-                            *state = self.algorithm.initial_state();
+                        if ui.selectable_value(&mut self.algorithm, algo, format!("{:?}", algo)).clicked() {
+                            self.hyperparameters = self.algorithm.hyperparameters();
+                            restart = true;
                         }
                     }
                 });
@@ -163,6 +736,13 @@ impl Easy21 {
             ui.add_space(5.0);
 
             Grid::new("grid").num_columns(2).show(ui, |ui| {
+                ui.label("Seed:");
+                ui.horizontal(|ui| {
+                    restart |= ui.add(egui::DragValue::new(&mut self.seed)).changed();
+                    restart |= ui.button("Restart").clicked();
+                });
+                ui.end_row();
+
                 ui.label("Episodes:");
                 ui.label(state.episodes().to_string());
                 ui.end_row();
@@ -170,14 +750,204 @@ impl Easy21 {
                 ui.label("Updates per frame:");
                 ui.label(self.updates_per_frame.to_string());
                 ui.end_row();
+
+                ui.label("RMS error:");
+                ui.label(format!("{:.4}", state.rms_error(&self.reference)));
+                ui.end_row();
+
+                if let Some(win_rate) = state.win_rate() {
+                    ui.label("Win rate:");
+                    ui.label(format!("{:.1}%", win_rate * 100.0));
+                    ui.end_row();
+                }
+            });
+
+            ui.collapsing("Hyperparameters", |ui| {
+                if self.hyperparameters.ui(ui, self.algorithm) {
+                    state.set_hyperparameters(self.hyperparameters);
+                }
+                if ui.button("Defaults").clicked() {
+                    self.hyperparameters = self.algorithm.hyperparameters();
+                    state.set_hyperparameters(self.hyperparameters);
+                }
             });
+
+            ui.collapsing("Rules", |ui| {
+                if env.rules.ui(ui) {
+                    self.reference = dp::Reference::new(&env.rules);
+                    // The errors of earlier runs were measured against other values.
+                    self.rms.clear();
+                    self.previous_rms.clear();
+                    restart = true;
+                }
+            });
+
+            ui.collapsing("Save / load", |ui| {
+                #[cfg(not(target_arch = "wasm32"))]
+                ui.horizontal(|ui| {
+                    ui.label("File:");
+                    ui.text_edit_singleline(&mut self.path);
+                });
+                ui.horizontal(|ui| {
+                    if ui.button("Save JSON").clicked() {
+                        file_action = Some(FileAction::Save(persistence::Format::Json));
+                    }
+                    #[cfg(not(target_arch = "wasm32"))]
+                    if ui.button("Save binary").clicked() {
+                        file_action = Some(FileAction::Save(persistence::Format::Binary));
+                    }
+                    if ui.button("Load").clicked() {
+                        file_action = Some(FileAction::Load);
+                    }
+                });
+                ui.label(&self.file_status);
+            });
+
+            ui.checkbox(&mut self.show_sweep, "λ sweep");
+            ui.checkbox(&mut self.show_baird, "Baird's counterexample");
+            ui.checkbox(&mut self.show_search, "Policy search");
         });
 
+        if restart {
+            self.restart();
+        }
+
+        if let Some(action) = file_action {
+            let result = match action {
+                FileAction::Save(format) => self.save(format, storage),
+                FileAction::Load => self.load(storage),
+            };
+            self.file_status = result.unwrap_or_else(|e| e);
+        }
+
+        if self.sweep.is_running() {
+            self.sweep.update(web_time::Duration::from_millis(5));
+        }
+        if self.search.is_running() {
+            self.search.update(web_time::Duration::from_millis(5));
+        }
+        let env = self.chart.get_data().env;
+        egui::Window::new("λ sweep")
+            .open(&mut self.show_sweep)
+            .show(ctx, |ui| {
+                self.sweep.ui(ui, &env, &self.reference.optimal, self.seed);
+            });
+        egui::Window::new("Baird's counterexample")
+            .open(&mut self.show_baird)
+            .show(ctx, |ui| {
+                self.baird.ui(ui, self.seed);
+            });
+        egui::Window::new("Policy search")
+            .open(&mut self.show_search)
+            .show(ctx, |ui| {
+                self.search.ui(ui, &env, self.seed);
+            });
+
         egui::CentralPanel::default().show(ctx, |ui| {
             self.ui(ui);
         });
     }
 
+    /// Starts the current algorithm from scratch, replaying the random numbers of `seed`.
+    fn restart(&mut self) {
+        self.keep_rms();
+        self.rng = StdRng::seed_from_u64(self.seed);
+        self.chart.get_data_mut().state = self.algorithm.initial_state(self.hyperparameters);
+    }
+
+    /// Moves the error curve of the current run to `previous_rms`, replacing the earlier
+    /// curve with the same label, and labels the next run.
+    fn keep_rms(&mut self) {
+        let rms = std::mem::take(&mut self.rms);
+        let label = run_label(self.algorithm, self.hyperparameters);
+        let previous = std::mem::replace(&mut self.rms_label, label);
+        if !rms.is_empty() {
+            self.previous_rms.retain(|(l, _)| *l != previous);
+            self.previous_rms.push((previous, rms));
+        }
+    }
+
+    fn snapshot(&self) -> persistence::Snapshot {
+        let learner = self.chart.get_data();
+        persistence::Snapshot::new(learner.env.rules, self.seed, learner.state.save())
+    }
+
+    /// Continues training from a snapshot. The random numbers start over from the saved
+    /// seed, so the continued run differs from one that was never interrupted.
+    fn restore(&mut self, snapshot: persistence::Snapshot) {
+        self.algorithm = snapshot.learner.algorithm();
+        self.hyperparameters = snapshot.learner.hyperparameters();
+        self.keep_rms();
+        let learner = self.chart.get_data_mut();
+        if learner.env.rules != snapshot.rules {
+            learner.env.rules = snapshot.rules;
+            self.reference = dp::Reference::new(&snapshot.rules);
+            self.previous_rms.clear();
+        }
+        learner.state = snapshot.learner.into_state();
+        self.seed = snapshot.seed;
+        self.rng = StdRng::seed_from_u64(self.seed);
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn save(
+        &self,
+        format: persistence::Format,
+        _storage: Option<&mut (dyn eframe::Storage + 'static)>,
+    ) -> Result<String, String> {
+        let path = std::path::Path::new(&self.path).with_extension(format.extension());
+        std::fs::write(&path, format.encode(&self.snapshot())).map_err(|e| e.to_string())?;
+        Ok(format!("Saved {}", path.display()))
+    }
+
+    /// Reads the file at `path`, as JSON if its extension is `.json` and as binary
+    /// otherwise.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn load(
+        &mut self,
+        _storage: Option<&mut (dyn eframe::Storage + 'static)>,
+    ) -> Result<String, String> {
+        let path = std::path::PathBuf::from(&self.path);
+        let format = if path.extension().map_or(false, |e| e == "json") {
+            persistence::Format::Json
+        } else {
+            persistence::Format::Binary
+        };
+        let bytes = std::fs::read(&path).map_err(|e| e.to_string())?;
+        let snapshot = format.decode(&bytes).map_err(|e| e.to_string())?;
+        self.restore(snapshot);
+        Ok(format!("Loaded {}", path.display()))
+    }
+
+    /// eframe's storage holds strings, so the web version only saves JSON.
+    #[cfg(target_arch = "wasm32")]
+    fn save(
+        &self,
+        format: persistence::Format,
+        storage: Option<&mut (dyn eframe::Storage + 'static)>,
+    ) -> Result<String, String> {
+        let storage = storage.ok_or("No storage available")?;
+        let json = String::from_utf8(format.encode(&self.snapshot())).map_err(|e| e.to_string())?;
+        storage.set_string(persistence::STORAGE_KEY, json);
+        storage.flush();
+        Ok("Saved to browser storage".to_owned())
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn load(
+        &mut self,
+        storage: Option<&mut (dyn eframe::Storage + 'static)>,
+    ) -> Result<String, String> {
+        let json = storage
+            .and_then(|storage| storage.get_string(persistence::STORAGE_KEY))
+            .ok_or("Nothing saved yet")?;
+        let snapshot = persistence::Format::Json
+            .decode(json.as_bytes())
+            .map_err(|e| e.to_string())?;
+        self.restore(snapshot);
+        Ok("Loaded from browser storage".to_owned())
+    }
+
     fn ui(&mut self, ui: &mut egui::Ui) {
         let space = ui.available_rect_before_wrap();
         let (left_rect, right_rect) = space.split_left_right_at_fraction(0.5);
@@ -189,12 +959,21 @@ impl Easy21 {
 
         // 2D
         let area = egui_plotter::EguiBackend::new(&right_ui).into_drawing_area();
-        let state = self.chart.get_data();
+        let state = &self.chart.get_data().state;
         if state.episodes() > 1 && ui.ctx().frame_nr() % 2 == 0 {
-            self.rms.push((state.episodes() as f64 / 1_000_000.0, state.rms_error()));
+            self.rms.push((
+                state.episodes() as f64 / 1_000_000.0,
+                state.rms_error(&self.reference),
+            ));
         }
 
-        let last = self.rms.last().map_or(0.1, |x| x.0).max(1.0);
+        let last = self
+            .previous_rms
+            .iter()
+            .map(|(_, rms)| rms)
+            .chain([&self.rms])
+            .filter_map(|rms| rms.last())
+            .fold(1.0, |a: f64, x| a.max(x.0));
 
         let mut chart = ChartBuilder::on(&area)
             .margin(200)
@@ -202,40 +981,184 @@ impl Easy21 {
             .margin_top(50)
             .x_label_area_size(100)
             .y_label_area_size(30)
-            .build_cartesian_2d(0.0..last, 0.0..10.0)
+            .build_cartesian_2d(0.0..last, 0.0..1.0)
             .unwrap();
 
         chart.configure_mesh().draw().unwrap();
 
+        for (i, (label, rms)) in self.previous_rms.iter().enumerate() {
+            let color = Palette99::pick(i).to_rgba();
+            chart
+                .draw_series(LineSeries::new(rms.clone(), &color))
+                .unwrap()
+                .label(label)
+                .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 15, y)], color));
+        }
+
         chart
             .draw_series(LineSeries::new(self.rms.clone(), &BLACK))
+            .unwrap()
+            .label(&self.rms_label)
+            .legend(|(x, y)| PathElement::new(vec![(x, y), (x + 15, y)], BLACK));
+
+        chart
+            .configure_series_labels()
+            .background_style(WHITE.mix(0.8))
+            .border_style(BLACK)
+            .draw()
             .unwrap();
     }
 }
 
+/// Names a run in the legend of the error chart.
+fn run_label(algorithm: Algorithm, h: Hyperparameters) -> String {
+    let mut label = format!("{:?}", algorithm);
+    if algorithm.has_trace_variants() {
+        label += &format!(" ({:?} traces)", h.trace);
+    }
+    if algorithm.is_linear() {
+        label += &format!(" ({:?})", h.features);
+    }
+    if matches!(algorithm, Algorithm::DynaQ | Algorithm::DynaQPlus) {
+        label += &format!(" ({} planning steps)", h.planning_steps);
+    }
+    label
+}
+
 trait Easy21State: HasV {
-    fn update(&mut self, rng: &mut rand::prelude::ThreadRng);
+    fn update(&mut self, env: &Easy21Env, rng: &mut StdRng);
     fn episodes(&self) -> i32;
     fn policy(&self, state: &State) -> Action;
-    fn rms_error(&self) -> f64;
+    /// Root mean squared error against the exact values of the policy being learned:
+    /// V^π or Q^π for prediction, Q* for control, and V^π of the current policy for
+    /// policy-gradient methods, which learn no action values.
+    fn rms_error(&self, reference: &dp::Reference) -> f64;
+    /// Fraction of recent episodes won, for learners that follow their own stochastic
+    /// policy rather than exploring around a greedy one.
+    fn win_rate(&self) -> Option<f64> {
+        None
+    }
+    fn set_hyperparameters(&mut self, _h: Hyperparameters) {}
+    /// Copies the learned values for saving.
+    fn save(&self) -> persistence::Learner;
+}
+
+/// The parameters of the game. The defaults are the rules of the original assignment.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Easy21Rules {
+    /// Card values are drawn uniformly from `min_card..=max_card`.
+    pub min_card: i32,
+    pub max_card: i32,
+    /// Probability that a drawn card is red, i.e. subtracts its value.
+    pub red_probability: f64,
+    /// The dealer keeps hitting until reaching at least this sum.
+    pub dealer_sticks_at: i32,
+    /// Sums outside of `min_sum..=max_sum` are bust.
+    pub min_sum: i32,
+    pub max_sum: i32,
+}
+
+impl Default for Easy21Rules {
+    fn default() -> Self {
+        Self {
+            min_card: 1,
+            max_card: 10,
+            red_probability: 1.0 / 3.0,
+            dealer_sticks_at: 17,
+            min_sum: 1,
+            max_sum: 21,
+        }
+    }
+}
+
+impl Easy21Rules {
+    fn card_values(&self) -> RangeInclusive<i32> {
+        self.min_card..=self.max_card
+    }
+
+    fn valid_sums(&self) -> RangeInclusive<i32> {
+        self.min_sum..=self.max_sum
+    }
+
+    fn is_bust(&self, x: i32) -> bool {
+        !self.valid_sums().contains(&x)
+    }
+
+    /// Whether every non-terminal state is within [`Easy21Env::MAX`], `red_probability`
+    /// is a probability and the dealer sticks at a valid sum. The controls in
+    /// [`Self::ui`] never leave these bounds.
+    fn is_supported(&self) -> bool {
+        1 <= self.min_card
+            && self.min_card <= self.max_card
+            && self.max_card <= 20
+            && -10 <= self.min_sum
+            && self.min_sum <= self.max_sum
+            && self.max_sum <= 30
+            && (0.0..=1.0).contains(&self.red_probability)
+            && self.valid_sums().contains(&self.dealer_sticks_at)
+    }
+
+    /// Shows controls for the rules and returns whether any of them changed. The bounds
+    /// keep every non-terminal state within [`Easy21Env::MAX`].
+    fn ui(&mut self, ui: &mut egui::Ui) -> bool {
+        let mut changed = false;
+        Grid::new("rules").num_columns(2).show(ui, |ui| {
+            ui.label("Card values:");
+            ui.horizontal(|ui| {
+                changed |= ui
+                    .add(egui::DragValue::new(&mut self.min_card).clamp_range(1..=self.max_card))
+                    .changed();
+                changed |= ui
+                    .add(egui::DragValue::new(&mut self.max_card).clamp_range(self.min_card..=20))
+                    .changed();
+            });
+            ui.end_row();
+
+            ui.label("Red probability:");
+            changed |= ui
+                .add(egui::Slider::new(&mut self.red_probability, 0.0..=1.0))
+                .changed();
+            ui.end_row();
+
+            ui.label("Dealer sticks at:");
+            let valid_sums = self.valid_sums();
+            changed |= ui
+                .add(egui::DragValue::new(&mut self.dealer_sticks_at).clamp_range(valid_sums))
+                .changed();
+            ui.end_row();
+
+            ui.label("Valid sums:");
+            ui.horizontal(|ui| {
+                changed |= ui
+                    .add(egui::DragValue::new(&mut self.min_sum).clamp_range(-10..=self.max_sum))
+                    .changed();
+                changed |= ui
+                    .add(egui::DragValue::new(&mut self.max_sum).clamp_range(self.min_sum..=30))
+                    .changed();
+            });
+            ui.end_row();
+        });
+        self.dealer_sticks_at = self.dealer_sticks_at.clamp(self.min_sum, self.max_sum);
+        changed
+    }
 }
 
-#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug, Serialize, Deserialize)]
 pub struct State {
     pub dealer: i32,
     pub player: i32,
 }
 
 impl State {
-    pub fn init<R: Rng>(rng: &mut R) -> Self {
+    pub fn init<R: Rng>(rng: &mut R, rules: &Easy21Rules) -> Self {
         Self {
-            dealer: Card::draw(rng).value,
-            player: Card::draw(rng).value,
+            dealer: Card::draw(rng, rules).value,
+            player: Card::draw(rng, rules).value,
         }
     }
 }
 
-#[derive(PartialEq, Eq, Hash, Clone, Copy)]
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug, Serialize, Deserialize)]
 pub enum Action {
     /// Draw another card from the deck. Then decide again.
     Hit,
@@ -262,27 +1185,28 @@ impl Card {
             }
     }
 
-    fn draw<R: Rng>(rng: &mut R) -> Self {
-        let v = Uniform::new_inclusive(1, 10);
+    fn draw<R: Rng>(rng: &mut R, rules: &Easy21Rules) -> Self {
+        let v = Uniform::from(rules.card_values());
         Self {
             value: v.sample(rng),
-            color: if rng.gen::<f64>() < (1.0 / 3.0) {
+            color: if rng.gen::<f64>() < rules.red_probability {
                 CardColor::Red
             } else {
                 CardColor::Black
             },
         }
     }
-}
-
-pub struct Sample {
-    state: State,
-    reward: i32,
-    terminal: bool,
-}
 
-fn is_bust(x: i32) -> bool {
-    !(1..=21).contains(&x)
+    /// All cards that `draw` can return, together with their probabilities.
+    fn outcomes(rules: &Easy21Rules) -> impl Iterator<Item = (Self, f64)> {
+        let p_value = 1.0 / rules.card_values().count() as f64;
+        let p_red = rules.red_probability;
+        rules.card_values().flat_map(move |value| {
+            [(CardColor::Red, p_red), (CardColor::Black, 1.0 - p_red)]
+                .into_iter()
+                .map(move |(color, p_color)| (Self { value, color }, p_value * p_color))
+        })
+    }
 }
 
 fn signum(x: i32) -> i32 {
@@ -293,35 +1217,40 @@ fn signum(x: i32) -> i32 {
     }
 }
 
-pub fn step<R: Rng>(rng: &mut R, state: State, action: Action) -> Sample {
+pub fn step<R: Rng>(
+    rng: &mut R,
+    rules: &Easy21Rules,
+    state: State,
+    action: Action,
+) -> Sample<State> {
     match action {
         Action::Hit => {
-            let card = Card::draw(rng);
+            let card = Card::draw(rng, rules);
             let player = card.add_to(state.player);
             Sample {
                 state: State { player, ..state },
-                reward: if is_bust(player) { -1 } else { 0 },
-                terminal: is_bust(player),
+                reward: if rules.is_bust(player) { -1.0 } else { 0.0 },
+                terminal: rules.is_bust(player),
             }
         }
         Action::Stick => {
             let mut dealer = state.dealer;
             loop {
-                if dealer >= 17 {
+                if dealer >= rules.dealer_sticks_at {
                     // Dealer sticks
                     break Sample {
                         state: State { dealer, ..state },
-                        reward: signum(state.player - dealer),
+                        reward: signum(state.player - dealer) as f64,
                         terminal: true,
                     };
                 } else {
                     // Dealer hits
-                    let card = Card::draw(rng);
+                    let card = Card::draw(rng, rules);
                     dealer = card.add_to(dealer);
-                    if is_bust(dealer) {
+                    if rules.is_bust(dealer) {
                         break Sample {
                             state: State { dealer, ..state },
-                            reward: 1,
+                            reward: 1.0,
                             terminal: true,
                         };
                     }
@@ -331,6 +1260,51 @@ pub fn step<R: Rng>(rng: &mut R, state: State, action: Action) -> Sample {
     }
 }
 
+/// Easy21 as an [`Environment`].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Easy21Env {
+    pub rules: Easy21Rules,
+}
+
+impl Easy21Env {
+    /// Player and dealer sums from -10 to 30 (offset by 10), which covers every
+    /// non-terminal state.
+    const MAX: [i32; 3] = [41, 41, 1];
+}
+
+impl Environment for Easy21Env {
+    type State = State;
+    type Action = Action;
+
+    const ACTIONS: &'static [Action] = &[Action::Hit, Action::Stick];
+
+    fn reset<R: Rng>(&self, rng: &mut R) -> State {
+        State::init(rng, &self.rules)
+    }
+
+    fn step<R: Rng>(&self, rng: &mut R, state: State, action: Action) -> Sample<State> {
+        step(rng, &self.rules, state, action)
+    }
+}
+
+impl Tabular for Easy21Env {
+    const STATES: usize = cube_size(Self::MAX);
+
+    #[inline]
+    fn state_index(state: &State) -> usize {
+        let point = [state.player + 10, state.dealer + 10, 0];
+        cube_index(point, Self::MAX)
+    }
+
+    #[inline]
+    fn action_index(action: &Action) -> usize {
+        match action {
+            Action::Hit => 0,
+            Action::Stick => 1,
+        }
+    }
+}
+
 #[inline]
 fn cube_index(point: [i32; 3], max: [i32; 3]) -> usize {
     let layers = point[2] * max[0] * max[1];
@@ -339,7 +1313,7 @@ fn cube_index(point: [i32; 3], max: [i32; 3]) -> usize {
     (layers + rows + bits) as usize
 }
 
-fn cube_size(max: [i32; 3]) -> usize {
+const fn cube_size(max: [i32; 3]) -> usize {
     (max[0] * max[1] * max[2]) as usize
 }
 
@@ -347,38 +1321,57 @@ pub trait HasV {
     fn get_v(&self, state: &State) -> f64;
 }
 
-#[derive(Clone)]
-pub struct V<T>(Vec<T>);
+pub struct V<T, E = Easy21Env>(Vec<T>, PhantomData<E>);
 
-impl<T> V<T> {
-    const MAX: [i32; 3] = [41, 41, 1];
+impl<T: Clone, E> Clone for V<T, E> {
+    fn clone(&self) -> Self {
+        V(self.0.clone(), PhantomData)
+    }
+}
+
+/// Serialized as the flat array of entries.
+impl<T: Serialize, E> Serialize for V<T, E> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.serialize(serializer)
+    }
+}
+
+impl<'de, T: Deserialize<'de>, E: Tabular> Deserialize<'de> for V<T, E> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let v = Vec::deserialize(deserializer)?;
+        if v.len() != E::STATES {
+            return Err(de::Error::invalid_length(v.len(), &"one entry per state"));
+        }
+        Ok(V(v, PhantomData))
+    }
+}
 
+impl<T, E: Tabular> V<T, E> {
     #[inline]
-    fn index(state: &State) -> usize {
-        let point = [state.player + 10, state.dealer + 10, 0];
-        cube_index(point, Self::MAX)
+    fn index(state: &E::State) -> usize {
+        E::state_index(state)
     }
 
     pub fn init(v: T) -> Self
     where
         T: Clone,
     {
-        V(vec![v; cube_size(Self::MAX)])
+        V(vec![v; E::STATES], PhantomData)
     }
 
-    pub fn get(&self, state: &State) -> T
+    pub fn get(&self, state: &E::State) -> T
     where
         T: Copy,
     {
         self.0[Self::index(state)]
     }
 
-    pub fn set(&mut self, state: &State, v: T) -> &mut Self {
+    pub fn set(&mut self, state: &E::State, v: T) -> &mut Self {
         self.0[Self::index(state)] = v;
         self
     }
 
-    fn update<F>(&mut self, state: &State, f: F) -> &mut Self
+    fn update<F>(&mut self, state: &E::State, f: F) -> &mut Self
     where
         F: Fn(&T) -> T,
     {
@@ -387,69 +1380,81 @@ impl<T> V<T> {
         self
     }
 
-    fn map<F>(&mut self, f: F) -> &mut Self
+    /// Applies `f` to the trace of an entry of this table in `traces`.
+    fn update_trace<F>(&self, traces: &mut Traces, state: &E::State, f: F)
     where
-        F: Fn(&T) -> T,
+        F: FnOnce(f64) -> f64,
     {
-        for v in self.0.iter_mut() {
-            *v = f(v);
-        }
-        self
+        traces.update(Self::index(state), f);
     }
 
-    fn zip_with<F, U>(&mut self, other: &V<U>, f: F) -> &mut Self
+    /// Combines every entry that has a trace in `traces` with its trace, and leaves the
+    /// others alone.
+    fn zip_traces<F>(&mut self, traces: &Traces, f: F) -> &mut Self
     where
-        F: Fn(&T, &U) -> T,
+        F: Fn(&T, f64) -> T,
     {
-        self.0 = self
-            .0
-            .iter()
-            .zip(other.0.iter())
-            .map(|(t, u)| f(t, u))
-            .collect();
+        for &(i, e) in traces.iter() {
+            self.0[i] = f(&self.0[i], e);
+        }
         self
     }
 }
 
-#[derive(Clone)]
-pub struct Q<T>(Vec<T>);
+pub struct Q<T, E = Easy21Env>(Vec<T>, PhantomData<E>);
+
+impl<T: Clone, E> Clone for Q<T, E> {
+    fn clone(&self) -> Self {
+        Q(self.0.clone(), PhantomData)
+    }
+}
+
+/// Serialized as the flat array of entries, one block of states per action.
+impl<T: Serialize, E> Serialize for Q<T, E> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.serialize(serializer)
+    }
+}
 
-impl<T> Q<T> {
-    const MAX: [i32; 3] = [41, 41, 2];
+impl<'de, T: Deserialize<'de>, E: Tabular> Deserialize<'de> for Q<T, E> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let q = Vec::deserialize(deserializer)?;
+        if q.len() != E::STATES * E::ACTIONS.len() {
+            return Err(de::Error::invalid_length(
+                q.len(),
+                &"one entry per state and action",
+            ));
+        }
+        Ok(Q(q, PhantomData))
+    }
+}
 
+impl<T, E: Tabular> Q<T, E> {
     #[inline]
-    fn index(state: &State, action: &Action) -> usize {
-        let point = [
-            state.player + 10,
-            state.dealer + 10,
-            match action {
-                Action::Hit => 0,
-                Action::Stick => 1,
-            },
-        ];
-        cube_index(point, Self::MAX)
+    fn index(state: &E::State, action: &E::Action) -> usize {
+        E::action_index(action) * E::STATES + E::state_index(state)
     }
 
     pub fn init(v: T) -> Self
     where
         T: Clone,
     {
-        Q(vec![v; cube_size(Self::MAX)])
+        Q(vec![v; E::STATES * E::ACTIONS.len()], PhantomData)
     }
 
-    pub fn get(&self, state: &State, action: &Action) -> T
+    pub fn get(&self, state: &E::State, action: &E::Action) -> T
     where
         T: Copy,
     {
         self.0[Self::index(state, action)]
     }
 
-    pub fn set(&mut self, state: &State, action: &Action, v: T) -> &mut Self {
+    pub fn set(&mut self, state: &E::State, action: &E::Action, v: T) -> &mut Self {
         self.0[Self::index(state, action)] = v;
         self
     }
 
-    fn update<F>(&mut self, state: &State, action: &Action, f: F) -> &mut Self
+    fn update<F>(&mut self, state: &E::State, action: &E::Action, f: F) -> &mut Self
     where
         F: Fn(&T) -> T,
     {
@@ -458,40 +1463,51 @@ impl<T> Q<T> {
         self
     }
 
-    fn map<F>(&mut self, f: F) -> &mut Self
+    /// Applies `f` to the trace of an entry of this table in `traces`.
+    fn update_trace<F>(&self, traces: &mut Traces, state: &E::State, action: &E::Action, f: F)
     where
-        F: Fn(&T) -> T,
+        F: FnOnce(f64) -> f64,
     {
-        for v in self.0.iter_mut() {
-            *v = f(v);
-        }
-        self
+        traces.update(Self::index(state, action), f);
     }
 
-    fn zip_with<F, U>(&mut self, other: &Q<U>, f: F) -> &mut Self
+    /// Combines every entry that has a trace in `traces` with its trace, and leaves the
+    /// others alone.
+    fn zip_traces<F>(&mut self, traces: &Traces, f: F) -> &mut Self
     where
-        F: Fn(&T, &U) -> T,
+        F: Fn(&T, f64) -> T,
     {
-        self.0 = self
-            .0
-            .iter()
-            .zip(other.0.iter())
-            .map(|(t, u)| f(t, u))
-            .collect();
+        for &(i, e) in traces.iter() {
+            self.0[i] = f(&self.0[i], e);
+        }
         self
     }
 }
 
-pub trait HasQ {
-    fn get_q(&self, state: &State, action: &Action) -> f64;
+pub trait HasQ<E: Environment = Easy21Env> {
+    fn get_q(&self, state: &E::State, action: &E::Action) -> f64;
 }
 
-impl HasQ for Q<(f64, i32)> {
-    fn get_q(&self, state: &State, action: &Action) -> f64 {
+impl<E: Tabular> HasQ<E> for Q<(f64, i32), E> {
+    fn get_q(&self, state: &E::State, action: &E::Action) -> f64 {
         self.get(state, action).0
     }
 }
 
+impl<E: Tabular> HasQ<E> for Q<(f64, f64), E> {
+    fn get_q(&self, state: &E::State, action: &E::Action) -> f64 {
+        self.get(state, action).0
+    }
+}
+
+/// Largest of the action values in `state`.
+fn max_q<E: Environment, Q: HasQ<E>>(q: &Q, state: &E::State) -> f64 {
+    E::ACTIONS
+        .iter()
+        .map(|a| q.get_q(state, a))
+        .fold(f64::NEG_INFINITY, f64::max)
+}
+
 pub fn example_policy<R: Rng>(_rng: &mut R, state: &State) -> Action {
     if state.player >= 20 {
         Action::Stick
@@ -500,16 +1516,23 @@ pub fn example_policy<R: Rng>(_rng: &mut R, state: &State) -> Action {
     }
 }
 
-pub fn episode<R: Rng, P: Fn(&mut R, &State) -> Action>(
+/// The state-action pairs visited in an episode, and the final reward.
+pub type Episode<E> = (
+    Vec<(<E as Environment>::State, <E as Environment>::Action)>,
+    f64,
+);
+
+pub fn episode<E: Environment, R: Rng, P: Fn(&mut R, &E::State) -> E::Action>(
+    env: &E,
     rng: &mut R,
     policy: P,
-) -> (Vec<(State, Action)>, i32) {
-    let mut state = State::init(rng);
+) -> Episode<E> {
+    let mut state = env.reset(rng);
     let mut state_actions = vec![];
     let reward = loop {
         let action = policy(rng, &state);
         state_actions.push((state, action));
-        let sample = step(rng, state, action);
+        let sample = env.step(rng, state, action);
         state = sample.state;
         if sample.terminal {
             break sample.reward;
@@ -518,19 +1541,23 @@ pub fn episode<R: Rng, P: Fn(&mut R, &State) -> Action>(
     (state_actions, reward)
 }
 
-#[derive(Clone)]
-pub struct MCState {
-    pub v: V<(f64, i32)>,
+pub struct MCState<E: Environment = Easy21Env> {
+    pub v: V<(f64, i32), E>,
     pub episodes: i32,
-    pub policy: fn(&mut rand::prelude::ThreadRng, &State) -> Action,
+    pub policy: fn(&mut StdRng, &E::State) -> E::Action,
+    pub hyperparameters: Hyperparameters,
 }
 
-impl MCState {
-    pub fn init(policy: fn(&mut rand::prelude::ThreadRng, &State) -> Action) -> Self {
+impl<E: Tabular> MCState<E> {
+    pub fn init(
+        hyperparameters: Hyperparameters,
+        policy: fn(&mut StdRng, &E::State) -> E::Action,
+    ) -> Self {
         Self {
             v: V::init((0.0, 0)),
             episodes: 0,
             policy,
+            hyperparameters,
         }
     }
 }
@@ -542,71 +1569,104 @@ impl HasV for MCState {
 }
 
 impl Easy21State for MCState {
-    fn update(&mut self, rng: &mut rand::prelude::ThreadRng) {
-        monte_carlo_prediction(rng, self.policy, self);
+    fn update(&mut self, env: &Easy21Env, rng: &mut StdRng) {
+        monte_carlo_prediction(env, rng, self.policy, self);
     }
     fn episodes(&self) -> i32 {
         self.episodes
     }
     fn policy(&self, state: &State) -> Action {
-        (self.policy)(&mut rand::thread_rng(), state)
+        // Fixed seed, so that a stochastic policy doesn't flicker in the plot.
+        (self.policy)(&mut StdRng::seed_from_u64(0), state)
+    }
+    fn rms_error(&self, reference: &dp::Reference) -> f64 {
+        reference.example_policy.rms_error_v(self)
     }
-    fn rms_error(&self) -> f64 {
-        0.0
+    fn set_hyperparameters(&mut self, h: Hyperparameters) {
+        self.hyperparameters = h;
+    }
+    fn save(&self) -> persistence::Learner {
+        persistence::Learner::MonteCarloPrediction {
+            v: self.v.clone(),
+            episodes: self.episodes,
+            hyperparameters: self.hyperparameters,
+        }
     }
 }
 
-pub fn monte_carlo_prediction<R: Rng, P: Fn(&mut R, &State) -> Action>(
+pub fn monte_carlo_prediction<E: Tabular, R: Rng, P: Fn(&mut R, &E::State) -> E::Action>(
+    env: &E,
     rng: &mut R,
     policy: P,
-    mc_state: &mut MCState,
+    mc_state: &mut MCState<E>,
 ) {
+    let h = mc_state.hyperparameters;
     mc_state.episodes += 1;
-    let (state_actions, reward) = episode(rng, policy);
+    let (state_actions, reward) = episode(env, rng, policy);
     for (state, _) in state_actions {
-        let (value, n) = mc_state.v.get(&state);
-        let new_n = n + 1;
-        let new_value = value + 1.0 / (new_n as f64) * (reward as f64 - value);
-        mc_state.v.set(&state, (new_value, new_n));
+        mc_state.v.update(&state, |(value, n)| {
+            (value + h.step_size.at(*n) * (reward - value), n + 1)
+        });
     }
 }
 
-fn epsilon_greedy<R: Rng, Q: HasQ>(rng: &mut R, eps: f64, q: &Q, state: &State) -> Action {
-    let q_hit = q.get_q(state, &Action::Hit);
-    let q_stick = q.get_q(state, &Action::Stick);
-    let threshold = if q_hit > q_stick {
-        eps / 2.0
-    } else {
-        1.0 - eps / 2.0
-    };
-    if rng.gen::<f64>() < threshold {
-        Action::Stick
+/// The action with the largest value. Ties go to the action listed last in
+/// `E::ACTIONS`.
+fn greedy<E: Environment, Q: HasQ<E>>(q: &Q, state: &E::State) -> E::Action {
+    let mut best = E::ACTIONS[0];
+    let mut best_value = q.get_q(state, &best);
+    for action in &E::ACTIONS[1..] {
+        let value = q.get_q(state, action);
+        if value >= best_value {
+            best = *action;
+            best_value = value;
+        }
+    }
+    best
+}
+
+fn epsilon_greedy<E: Environment, R: Rng, Q: HasQ<E>>(
+    rng: &mut R,
+    eps: f64,
+    q: &Q,
+    state: &E::State,
+) -> E::Action {
+    if rng.gen::<f64>() < eps {
+        random_action::<E, R>(rng)
     } else {
-        Action::Hit
+        greedy(q, state)
     }
 }
 
-fn greedy_episode<R: Rng>(rng: &mut R, mc_state: &MCControlState) -> (Vec<(State, Action)>, i32) {
-    episode(rng, |rng, state| {
-        let visited = mc_state.v.get(state).1 as f64;
-        let eps = 1.0 / (10.0 + visited / 100_000.0);
-        epsilon_greedy(rng, eps, &mc_state.q, state)
+fn random_action<E: Environment, R: Rng>(rng: &mut R) -> E::Action {
+    E::ACTIONS[rng.gen_range(0..E::ACTIONS.len())]
+}
+
+fn greedy_episode<E: Tabular, R: Rng, X: Exploration<E>>(
+    env: &E,
+    rng: &mut R,
+    exploration: &X,
+    mc_state: &MCControlState<E>,
+) -> Episode<E> {
+    episode(env, rng, |rng, state| {
+        exploration.choose(rng, &mc_state.q, state)
     })
 }
 
-#[derive(Clone)]
-pub struct MCControlState {
-    pub v: V<(f64, i32)>,
-    pub q: Q<(f64, i32)>,
+pub struct MCControlState<E = Easy21Env> {
+    pub v: V<(f64, i32), E>,
+    pub q: Q<(f64, i32), E>,
     pub episodes: i32,
+    pub hyperparameters: Hyperparameters,
 }
 
-impl MCControlState {
-    pub fn init() -> Self {
+impl<E: Tabular> MCControlState<E> {
+    pub fn init(hyperparameters: Hyperparameters) -> Self {
         Self {
             v: V::init((0.0, 0)),
             q: Q::init((0.0, 0)),
             episodes: 0,
+            hyperparameters,
         }
     }
 }
@@ -618,8 +1678,9 @@ impl HasV for MCControlState {
 }
 
 impl Easy21State for MCControlState {
-    fn update(&mut self, rng: &mut rand::prelude::ThreadRng) {
-        monte_carlo_control(rng, self);
+    fn update(&mut self, env: &Easy21Env, rng: &mut StdRng) {
+        let exploration = self.hyperparameters.exploration;
+        monte_carlo_control(env, rng, &exploration, self);
     }
     fn episodes(&self) -> i32 {
         self.episodes
@@ -631,52 +1692,266 @@ impl Easy21State for MCControlState {
             Action::Stick
         }
     }
-    fn rms_error(&self) -> f64 {
-        self.q.rms_error()
+    fn rms_error(&self, reference: &dp::Reference) -> f64 {
+        self.q.rms_error(&reference.optimal)
     }
-}
+    fn set_hyperparameters(&mut self, h: Hyperparameters) {
+        self.hyperparameters = h;
+    }
+    fn save(&self) -> persistence::Learner {
+        persistence::Learner::MonteCarloControl {
+            v: self.v.clone(),
+            q: self.q.clone(),
+            episodes: self.episodes,
+            hyperparameters: self.hyperparameters,
+        }
+    }
+}
 
-pub fn monte_carlo_control<R: Rng>(rng: &mut R, mc_state: &mut MCControlState) {
-    let (state_actions, reward) = greedy_episode(rng, mc_state);
+pub fn monte_carlo_control<E: Tabular, R: Rng, X: Exploration<E>>(
+    env: &E,
+    rng: &mut R,
+    exploration: &X,
+    mc_state: &mut MCControlState<E>,
+) {
+    let h = mc_state.hyperparameters;
+    let (state_actions, reward) = greedy_episode(env, rng, exploration, mc_state);
     for (state, action) in state_actions {
         // Update Q
-        let (value, n) = mc_state.q.get(&state, &action);
-        let new_n = n + 1;
-        let new_value = value + 1.0 / (new_n as f64) * (reward as f64 - value);
-        mc_state.q.set(&state, &action, (new_value, new_n));
+        mc_state.q.update(&state, &action, |(value, n)| {
+            (value + h.step_size.at(*n) * (reward - value), n + 1)
+        });
 
         // Update V
-        let q_hit = mc_state.q.get(&state, &Action::Hit).0;
-        let q_stick = mc_state.q.get(&state, &Action::Stick).0;
-        mc_state
-            .v
-            .update(&state, |(_, n)| (q_hit.max(q_stick), n + 1));
+        let v = max_q(&mc_state.q, &state);
+        mc_state.v.update(&state, |(_, n)| (v, n + 1));
     }
     // Inc episodes
     mc_state.episodes += 1;
-    if mc_state.episodes == 200_000_000 {
-        println!(
-            "{:?}",
-            mc_state.q.0.iter().map(|(v, _)| v).collect::<Vec<_>>()
-        );
+}
+
+/// A policy that the learners can store.
+pub type Policy<E> = fn(&mut StdRng, &<E as Environment>::State) -> <E as Environment>::Action;
+
+/// How off-policy Monte Carlo averages the returns of the behaviour policy.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Sampling {
+    /// Plain average of the returns scaled by their importance sampling ratios. Unbiased,
+    /// but the variance can be large.
+    Ordinary,
+    /// Average weighted by the importance sampling ratios. Biased, but with much lower
+    /// variance.
+    Weighted,
+}
+
+/// Off-policy Monte Carlo. Episodes come from a behaviour policy that explores around
+/// the target policy, and every entry of `q` holds the estimate of the target policy's
+/// action value together with its total weight: the number of returns for ordinary
+/// importance sampling, the sum of the ratios for weighted importance sampling.
+pub struct OffPolicyMCState<E: Environment = Easy21Env> {
+    pub q: Q<(f64, f64), E>,
+    pub episodes: i32,
+    pub sampling: Sampling,
+    /// Deterministic target policy for prediction. `None` for control, where the target
+    /// policy is greedy with respect to `q`.
+    pub target: Option<Policy<E>>,
+    pub hyperparameters: Hyperparameters,
+}
+
+impl<E: Tabular> OffPolicyMCState<E> {
+    pub fn init(
+        hyperparameters: Hyperparameters,
+        sampling: Sampling,
+        target: Option<Policy<E>>,
+    ) -> Self {
+        Self {
+            q: Q::init((0.0, 0.0)),
+            episodes: 0,
+            sampling,
+            target,
+            hyperparameters,
+        }
+    }
+}
+
+impl HasV for OffPolicyMCState {
+    fn get_v(&self, state: &State) -> f64 {
+        self.q.get(state, &Easy21State::policy(self, state)).0
+    }
+}
+
+impl Easy21State for OffPolicyMCState {
+    fn update(&mut self, env: &Easy21Env, rng: &mut StdRng) {
+        let exploration = self.hyperparameters.exploration;
+        match self.target {
+            Some(target) => off_policy_monte_carlo_prediction(env, rng, &exploration, target, self),
+            None => off_policy_monte_carlo_control(env, rng, &exploration, self),
+        }
+    }
+    fn episodes(&self) -> i32 {
+        self.episodes
+    }
+    fn policy(&self, state: &State) -> Action {
+        match self.target {
+            // Fixed seed, so that a stochastic policy doesn't flicker in the plot.
+            Some(target) => target(&mut StdRng::seed_from_u64(0), state),
+            None => greedy(&self.q, state),
+        }
+    }
+    fn rms_error(&self, reference: &dp::Reference) -> f64 {
+        match self.target {
+            Some(_) => reference.example_policy.rms_error(&self.q),
+            None => reference.optimal.rms_error(&self.q),
+        }
+    }
+    fn set_hyperparameters(&mut self, h: Hyperparameters) {
+        self.hyperparameters = h;
+    }
+    fn save(&self) -> persistence::Learner {
+        let q = self.q.clone();
+        let sampling = self.sampling;
+        let episodes = self.episodes;
+        let hyperparameters = self.hyperparameters;
+        match self.target {
+            Some(_) => persistence::Learner::OffPolicyMCPrediction {
+                q,
+                sampling,
+                episodes,
+                hyperparameters,
+            },
+            None => persistence::Learner::OffPolicyMCControl {
+                q,
+                sampling,
+                episodes,
+                hyperparameters,
+            },
+        }
     }
 }
 
-#[derive(Clone)]
-pub struct TDState {
-    pub v: V<(f64, i32)>,
-    pub eligibility_traces: V<f64>,
+/// Action values under which only the target policy's action is greedy, so that an
+/// exploration strategy explores around the target policy.
+struct TargetValues<E: Environment>(E::Action);
+
+impl<E: Environment> HasQ<E> for TargetValues<E> {
+    fn get_q(&self, _state: &E::State, action: &E::Action) -> f64 {
+        if *action == self.0 {
+            1.0
+        } else {
+            0.0
+        }
+    }
+}
+
+impl<E: Environment> Counted<E> for TargetValues<E> {
+    fn count(&self, _state: &E::State, _action: &E::Action) -> i32 {
+        0
+    }
+}
+
+/// Evaluates the deterministic policy `target` from episodes of a behaviour policy that
+/// explores around it.
+pub fn off_policy_monte_carlo_prediction<
+    E: Tabular,
+    R: Rng,
+    X: Exploration<E>,
+    P: Fn(&mut R, &E::State) -> E::Action,
+>(
+    env: &E,
+    rng: &mut R,
+    exploration: &X,
+    target: P,
+    mc_state: &mut OffPolicyMCState<E>,
+) {
+    let episode = episode(env, rng, |rng, state| {
+        let values = TargetValues::<E>(target(rng, state));
+        exploration.choose(rng, &values, state)
+    });
+    let behaviour = episode
+        .0
+        .iter()
+        .map(|(state, action)| {
+            let values = TargetValues::<E>(target(rng, state));
+            exploration.probability(&values, state, action)
+        })
+        .collect();
+    off_policy_update(mc_state, episode, behaviour, |_, state| target(rng, state));
+}
+
+/// Learns the greedy policy from episodes of a behaviour policy that explores with
+/// respect to `q`.
+pub fn off_policy_monte_carlo_control<E: Tabular, R: Rng, X: Exploration<E>>(
+    env: &E,
+    rng: &mut R,
+    exploration: &X,
+    mc_state: &mut OffPolicyMCState<E>,
+) {
+    let episode = episode(env, rng, |rng, state| {
+        exploration.choose(rng, &mc_state.q, state)
+    });
+    let behaviour = episode
+        .0
+        .iter()
+        .map(|(state, action)| exploration.probability(&mc_state.q, state, action))
+        .collect();
+    off_policy_update(mc_state, episode, behaviour, |q, state| greedy(q, state));
+}
+
+/// Folds the return of an episode into `mc_state.q`, going backwards from the last step
+/// and scaling by the importance sampling ratio of the steps after each pair. `behaviour`
+/// holds the probability with which the behaviour policy took each action. The target
+/// policy is read after updating each pair, since in control it depends on `q`.
+fn off_policy_update<E: Tabular, T: FnMut(&Q<(f64, f64), E>, &E::State) -> E::Action>(
+    mc_state: &mut OffPolicyMCState<E>,
+    (state_actions, reward): Episode<E>,
+    behaviour: Vec<f64>,
+    mut target: T,
+) {
+    let mut ratio = 1.0;
+    for ((state, action), b) in state_actions.into_iter().zip(behaviour).rev() {
+        match mc_state.sampling {
+            Sampling::Ordinary => mc_state.q.update(&state, &action, |(value, n)| {
+                let n = n + 1.0;
+                (value + (ratio * reward - value) / n, n)
+            }),
+            Sampling::Weighted => mc_state.q.update(&state, &action, |(value, c)| {
+                let c = c + ratio;
+                (value + ratio / c * (reward - value), c)
+            }),
+        };
+        if target(&mc_state.q, &state) == action {
+            ratio /= b;
+        } else {
+            // The target policy never takes this action, so the return counts with
+            // weight zero for all earlier steps.
+            match mc_state.sampling {
+                Sampling::Ordinary => ratio = 0.0,
+                Sampling::Weighted => break,
+            }
+        }
+    }
+    mc_state.episodes += 1;
+}
+
+pub struct TDState<E: Environment = Easy21Env> {
+    pub v: V<(f64, i32), E>,
+    pub eligibility_traces: Traces,
     pub episodes: i32,
-    pub policy: fn(&mut rand::prelude::ThreadRng, &State) -> Action,
+    pub policy: fn(&mut StdRng, &E::State) -> E::Action,
+    pub hyperparameters: Hyperparameters,
 }
 
-impl TDState {
-    pub fn init(policy: fn(&mut rand::prelude::ThreadRng, &State) -> Action) -> Self {
+impl<E: Tabular> TDState<E> {
+    pub fn init(
+        hyperparameters: Hyperparameters,
+        policy: fn(&mut StdRng, &E::State) -> E::Action,
+    ) -> Self {
         Self {
             v: V::init((0.0, 0)),
-            eligibility_traces: V::init(0.0),
+            eligibility_traces: Traces::default(),
             episodes: 0,
             policy,
+            hyperparameters,
         }
     }
 }
@@ -688,46 +1963,66 @@ impl HasV for TDState {
 }
 
 impl Easy21State for TDState {
-    fn update(&mut self, rng: &mut rand::prelude::ThreadRng) {
-        td_lambda_prediction(rng, 0.5, self.policy, self);
+    fn update(&mut self, env: &Easy21Env, rng: &mut StdRng) {
+        td_lambda_prediction(env, rng, self.policy, self);
     }
     fn episodes(&self) -> i32 {
         self.episodes
     }
     fn policy(&self, state: &State) -> Action {
-        (self.policy)(&mut rand::thread_rng(), state)
+        // Fixed seed, so that a stochastic policy doesn't flicker in the plot.
+        (self.policy)(&mut StdRng::seed_from_u64(0), state)
+    }
+    fn rms_error(&self, reference: &dp::Reference) -> f64 {
+        reference.example_policy.rms_error_v(self)
     }
-    fn rms_error(&self) -> f64 {
-        0.0
+    fn set_hyperparameters(&mut self, h: Hyperparameters) {
+        self.hyperparameters = h;
+    }
+    fn save(&self) -> persistence::Learner {
+        persistence::Learner::TDLambdaPrediction {
+            v: self.v.clone(),
+            episodes: self.episodes,
+            hyperparameters: self.hyperparameters,
+        }
     }
 }
 
 /// One episode, will be looped over by the main loop.
-pub fn td_lambda_prediction<R: Rng, P: Fn(&mut R, &State) -> Action>(
+pub fn td_lambda_prediction<E: Tabular, R: Rng, P: Fn(&mut R, &E::State) -> E::Action>(
+    env: &E,
     rng: &mut R,
-    lambda: f64,
     policy: P,
-    td_state: &mut TDState,
+    td_state: &mut TDState<E>,
 ) {
-    td_state.eligibility_traces.map(|_| 0.0);
-    let mut state = State::init(rng);
+    let h = td_state.hyperparameters;
+    td_state.eligibility_traces.clear();
+    let mut state = env.reset(rng);
     loop {
         let action = policy(rng, &state);
-        let sample = step(rng, state, action);
+        let sample = env.step(rng, state, action);
 
         let next_state = sample.state;
 
         // Update eligibility traces
-        td_state.eligibility_traces.map(|v| v * lambda);
-        td_state.eligibility_traces.update(&state, |v| v + 1.0);
+        td_state.eligibility_traces.decay(h.lambda);
+        let alpha = h.step_size.at(td_state.v.get(&state).1);
+        td_state
+            .v
+            .update_trace(&mut td_state.eligibility_traces, &state, |e| {
+                h.trace.visit(e, alpha)
+            });
 
-        let td_error =
-            (sample.reward as f64) + td_state.v.get(&next_state).0 - td_state.v.get(&state).0;
+        let next_value = if sample.terminal {
+            0.0
+        } else {
+            td_state.v.get(&next_state).0
+        };
+        let td_error = sample.reward + next_value - td_state.v.get(&state).0;
         td_state
             .v
-            .zip_with(&td_state.eligibility_traces, |(v, n), eligibility| {
-                let alpha = 1.0 / (10.0 + *n as f64);
-                (v + alpha * td_error * eligibility, *n)
+            .zip_traces(&td_state.eligibility_traces, |(v, n), eligibility| {
+                (v + h.step_size.at(*n) * td_error * eligibility, *n)
             });
         td_state.v.update(&state, |(v, n)| (*v, n + 1));
 
@@ -740,23 +2035,42 @@ pub fn td_lambda_prediction<R: Rng, P: Fn(&mut R, &State) -> Action>(
     td_state.episodes += 1;
 }
 
-#[derive(Clone)]
-pub struct TDControlState {
-    pub v: V<(f64, i32)>,
-    pub q: Q<(f64, i32)>,
-    pub eligibility_traces: Q<f64>,
+/// The tabular TD control algorithms that share [`TDControlState`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TDControlMethod {
+    /// On-policy: bootstraps from the action the ε-greedy policy takes next.
+    Sarsa,
+    /// Off-policy: bootstraps from the greedy action, without traces.
+    QLearning,
+    /// Off-policy like Q-learning, with traces that are cut after exploratory actions.
+    WatkinsQLambda,
+    /// Bootstraps from the expected value under the ε-greedy policy, without traces.
+    ExpectedSarsa,
+    /// On-policy, with the sampled rewards of the next n steps.
+    NStepSarsa,
+    /// Off-policy over n steps, following the sampled actions as long as they are greedy.
+    NStepTreeBackup,
+}
+
+pub struct TDControlState<E = Easy21Env> {
+    pub v: V<(f64, i32), E>,
+    pub q: Q<(f64, i32), E>,
+    pub eligibility_traces: Traces,
     pub episodes: i32,
-    pub rms_error: f64,
+    pub method: TDControlMethod,
+    pub hyperparameters: Hyperparameters,
 }
 
-impl TDControlState {
-    pub fn init() -> Self {
+impl<E: Tabular> TDControlState<E> {
+    /// SARSA(λ). Set `method` for the other algorithms.
+    pub fn init(hyperparameters: Hyperparameters) -> Self {
         Self {
             v: V::init((0.0, 0)),
             q: Q::init((0.0, 0)),
-            eligibility_traces: Q::init(0.0),
+            eligibility_traces: Traces::default(),
             episodes: 0,
-            rms_error: 0.0,
+            method: TDControlMethod::Sarsa,
+            hyperparameters,
         }
     }
 }
@@ -768,8 +2082,9 @@ impl HasV for TDControlState {
 }
 
 impl Easy21State for TDControlState {
-    fn update(&mut self, rng: &mut rand::prelude::ThreadRng) {
-        td_lambda_control(rng, 0.6, self);
+    fn update(&mut self, env: &Easy21Env, rng: &mut StdRng) {
+        let exploration = self.hyperparameters.exploration;
+        td_control(env, rng, &exploration, self);
     }
     fn episodes(&self) -> i32 {
         self.episodes
@@ -781,155 +2096,575 @@ impl Easy21State for TDControlState {
             Action::Stick
         }
     }
-    fn rms_error(&self) -> f64 {
-        self.rms_error
+    fn rms_error(&self, reference: &dp::Reference) -> f64 {
+        self.q.rms_error(&reference.optimal)
+    }
+    fn set_hyperparameters(&mut self, h: Hyperparameters) {
+        self.hyperparameters = h;
+    }
+    fn save(&self) -> persistence::Learner {
+        let v = self.v.clone();
+        let q = self.q.clone();
+        let episodes = self.episodes;
+        let hyperparameters = self.hyperparameters;
+        match self.method {
+            TDControlMethod::Sarsa => persistence::Learner::TDLambdaControl {
+                v,
+                q,
+                episodes,
+                hyperparameters,
+            },
+            TDControlMethod::QLearning => persistence::Learner::QLearning {
+                v,
+                q,
+                episodes,
+                hyperparameters,
+            },
+            TDControlMethod::WatkinsQLambda => persistence::Learner::WatkinsQLambda {
+                v,
+                q,
+                episodes,
+                hyperparameters,
+            },
+            TDControlMethod::ExpectedSarsa => persistence::Learner::ExpectedSarsa {
+                v,
+                q,
+                episodes,
+                hyperparameters,
+            },
+            TDControlMethod::NStepSarsa => persistence::Learner::NStepSarsa {
+                v,
+                q,
+                episodes,
+                hyperparameters,
+            },
+            TDControlMethod::NStepTreeBackup => persistence::Learner::NStepTreeBackup {
+                v,
+                q,
+                episodes,
+                hyperparameters,
+            },
+        }
     }
 }
 
-pub fn td_lambda_control<R: Rng>(rng: &mut R, lambda: f64, td_state: &mut TDControlState) {
-    td_state.eligibility_traces.map(|_| 0.0);
-    let mut state = State::init(rng);
+/// One episode of the algorithm selected by `td_state.method`.
+pub fn td_control<E: Tabular, R: Rng, X: Exploration<E>>(
+    env: &E,
+    rng: &mut R,
+    exploration: &X,
+    td_state: &mut TDControlState<E>,
+) {
+    match td_state.method {
+        TDControlMethod::Sarsa => td_lambda_control(env, rng, exploration, td_state),
+        TDControlMethod::QLearning => q_learning(env, rng, exploration, td_state),
+        TDControlMethod::WatkinsQLambda => watkins_q_lambda(env, rng, exploration, td_state),
+        TDControlMethod::ExpectedSarsa => expected_sarsa(env, rng, exploration, td_state),
+        TDControlMethod::NStepSarsa => n_step_control(env, rng, exploration, false, td_state),
+        TDControlMethod::NStepTreeBackup => n_step_control(env, rng, exploration, true, td_state),
+    }
+}
 
-    let eps = 1.0 / (10.0 + td_state.v.get(&state).1 as f64 / 10_000.0);
-    let mut action = epsilon_greedy(rng, eps, &td_state.q, &state);
+pub fn td_lambda_control<E: Tabular, R: Rng, X: Exploration<E>>(
+    env: &E,
+    rng: &mut R,
+    exploration: &X,
+    td_state: &mut TDControlState<E>,
+) {
+    let h = td_state.hyperparameters;
+    td_state.eligibility_traces.clear();
+    let mut state = env.reset(rng);
 
-    loop {
-        let sample = step(rng, state, action);
-        let next_state = sample.state;
+    let mut action = exploration.choose(rng, &td_state.q, &state);
 
-        let eps = 1.0 / (10.0 + td_state.v.get(&next_state).1 as f64 / 10_000.0);
-        let next_action = epsilon_greedy(rng, eps, &td_state.q, &next_state);
+    loop {
+        let sample = env.step(rng, state, action);
 
         // Update eligibility traces
-        td_state.eligibility_traces.map(|v| v * lambda);
+        td_state.eligibility_traces.decay(h.lambda);
+        let alpha = h.step_size.at(td_state.q.get(&state, &action).1);
         td_state
-            .eligibility_traces
-            .update(&state, &action, |v| v + 1.0);
+            .q
+            .update_trace(&mut td_state.eligibility_traces, &state, &action, |e| {
+                h.trace.visit(e, alpha)
+            });
+
+        let (next_value, next) = if sample.terminal {
+            (0.0, None)
+        } else {
+            let next_state = sample.state;
+            let next_action = exploration.choose(rng, &td_state.q, &next_state);
+            (
+                td_state.q.get(&next_state, &next_action).0,
+                Some((next_state, next_action)),
+            )
+        };
 
-        let td_error = (sample.reward as f64) + td_state.q.get(&next_state, &next_action).0
-            - td_state.q.get(&state, &action).0;
+        let td_error = sample.reward + next_value - td_state.q.get(&state, &action).0;
         td_state
             .q
-            .zip_with(&td_state.eligibility_traces, |(v, n), eligibility| {
-                let alpha = 1.0 / (10.0 + *n as f64);
-                (v + alpha * td_error * eligibility, *n)
+            .zip_traces(&td_state.eligibility_traces, |(v, n), eligibility| {
+                (v + h.step_size.at(*n) * td_error * eligibility, *n)
             });
         td_state.q.update(&state, &action, |(v, n)| (*v, *n + 1));
 
         // Update V
-        let q_hit = td_state.q.get(&state, &Action::Hit).0;
-        let q_stick = td_state.q.get(&state, &Action::Stick).0;
+        let v = max_q(&td_state.q, &state);
+        td_state.v.update(&state, |(_, n)| (v, n + 1));
+
+        match next {
+            None => break,
+            Some((next_state, next_action)) => {
+                state = next_state;
+                action = next_action;
+            }
+        }
+    }
+    td_state.episodes += 1;
+}
+
+/// Q-learning: Watkins's Q(λ) with λ = 0.
+pub fn q_learning<E: Tabular, R: Rng, X: Exploration<E>>(
+    env: &E,
+    rng: &mut R,
+    exploration: &X,
+    td_state: &mut TDControlState<E>,
+) {
+    q_lambda(env, rng, exploration, 0.0, td_state);
+}
+
+/// Watkins's Q(λ), with λ from the hyperparameters.
+pub fn watkins_q_lambda<E: Tabular, R: Rng, X: Exploration<E>>(
+    env: &E,
+    rng: &mut R,
+    exploration: &X,
+    td_state: &mut TDControlState<E>,
+) {
+    let lambda = td_state.hyperparameters.lambda;
+    q_lambda(env, rng, exploration, lambda, td_state);
+}
+
+/// Behaves according to `exploration` but learns the values of the greedy policy. The
+/// traces only follow the greedy policy, so they are cut whenever the next action is
+/// exploratory.
+fn q_lambda<E: Tabular, R: Rng, X: Exploration<E>>(
+    env: &E,
+    rng: &mut R,
+    exploration: &X,
+    lambda: f64,
+    td_state: &mut TDControlState<E>,
+) {
+    let h = td_state.hyperparameters;
+    td_state.eligibility_traces.clear();
+    let mut state = env.reset(rng);
+
+    let mut action = exploration.choose(rng, &td_state.q, &state);
+
+    loop {
+        let sample = env.step(rng, state, action);
+
+        let alpha = h.step_size.at(td_state.q.get(&state, &action).1);
         td_state
-            .v
-            .update(&state, |(_, n)| (q_hit.max(q_stick), n + 1));
+            .q
+            .update_trace(&mut td_state.eligibility_traces, &state, &action, |e| {
+                h.trace.visit(e, alpha)
+            });
+
+        let (next_value, next) = if sample.terminal {
+            (0.0, None)
+        } else {
+            let next_state = sample.state;
+            let next_action = exploration.choose(rng, &td_state.q, &next_state);
+            let best = max_q(&td_state.q, &next_state);
+            // Ties with the best action count as greedy.
+            let greedy = td_state.q.get(&next_state, &next_action).0 == best;
+            (best, Some((next_state, next_action, greedy)))
+        };
+
+        let td_error = sample.reward + next_value - td_state.q.get(&state, &action).0;
+        td_state
+            .q
+            .zip_traces(&td_state.eligibility_traces, |(v, n), eligibility| {
+                (v + h.step_size.at(*n) * td_error * eligibility, *n)
+            });
+        td_state.q.update(&state, &action, |(v, n)| (*v, *n + 1));
+
+        // Update V
+        let v = max_q(&td_state.q, &state);
+        td_state.v.update(&state, |(_, n)| (v, n + 1));
+
+        match next {
+            None => break,
+            Some((next_state, next_action, greedy)) => {
+                if greedy {
+                    td_state.eligibility_traces.decay(lambda);
+                } else {
+                    td_state.eligibility_traces.clear();
+                }
+                state = next_state;
+                action = next_action;
+            }
+        }
+    }
+    td_state.episodes += 1;
+}
+
+/// Expected SARSA: like SARSA, but bootstraps from the expectation over the next action
+/// instead of the sampled one, which removes the variance of the next action choice.
+pub fn expected_sarsa<E: Tabular, R: Rng, X: Exploration<E>>(
+    env: &E,
+    rng: &mut R,
+    exploration: &X,
+    td_state: &mut TDControlState<E>,
+) {
+    let h = td_state.hyperparameters;
+    let mut state = env.reset(rng);
+
+    loop {
+        let action = exploration.choose(rng, &td_state.q, &state);
+        let sample = env.step(rng, state, action);
+
+        let next_value = if sample.terminal {
+            0.0
+        } else {
+            exploration.expected_q(&td_state.q, &sample.state)
+        };
+        td_state.q.update(&state, &action, |(v, n)| {
+            let td_error = sample.reward + next_value - v;
+            (v + h.step_size.at(*n) * td_error, n + 1)
+        });
+
+        // Update V
+        let v = max_q(&td_state.q, &state);
+        td_state.v.update(&state, |(_, n)| (v, n + 1));
 
         if sample.terminal {
             break;
         } else {
-            state = next_state;
-            action = next_action;
+            state = sample.state;
+        }
+    }
+    td_state.episodes += 1;
+}
+
+/// A state-action pair and its outcome.
+type Transition<E> = (
+    <E as Environment>::State,
+    <E as Environment>::Action,
+    Sample<<E as Environment>::State>,
+);
+
+/// n-step SARSA, or n-step tree backup if `tree_backup` is set, with n from the
+/// hyperparameters. The last n transitions are kept in a buffer, and each update moves
+/// the oldest pair towards the return over the buffer.
+fn n_step_control<E: Tabular, R: Rng, X: Exploration<E>>(
+    env: &E,
+    rng: &mut R,
+    exploration: &X,
+    tree_backup: bool,
+    td_state: &mut TDControlState<E>,
+) {
+    let h = td_state.hyperparameters;
+    let n = h.n.max(1);
+    let mut buffer = VecDeque::with_capacity(n);
+    let mut state = env.reset(rng);
+
+    let mut action = exploration.choose(rng, &td_state.q, &state);
+
+    loop {
+        let sample = env.step(rng, state, action);
+        buffer.push_back((state, action, sample));
+
+        if sample.terminal {
+            while !buffer.is_empty() {
+                n_step_update(td_state, &buffer, 0.0, tree_backup);
+                buffer.pop_front();
+            }
+            break;
+        }
+
+        let next_state = sample.state;
+        let next_action = exploration.choose(rng, &td_state.q, &next_state);
+
+        if buffer.len() == n {
+            // Tree backup takes the expectation under the greedy target policy.
+            let bootstrap = if tree_backup {
+                max_q(&td_state.q, &next_state)
+            } else {
+                td_state.q.get(&next_state, &next_action).0
+            };
+            n_step_update(td_state, &buffer, bootstrap, tree_backup);
+            buffer.pop_front();
         }
+
+        state = next_state;
+        action = next_action;
     }
     td_state.episodes += 1;
-    if td_state.episodes % 1000 == 0 {
-        td_state.rms_error = td_state.q.rms_error();
+}
+
+/// Moves the value of the oldest pair in `buffer` towards its return, where `bootstrap`
+/// is the value after the newest transition. Tree backup replaces the rest of the return
+/// by the greedy value wherever a later action was not greedy.
+fn n_step_update<E: Tabular>(
+    td_state: &mut TDControlState<E>,
+    buffer: &VecDeque<Transition<E>>,
+    bootstrap: f64,
+    tree_backup: bool,
+) {
+    let h = td_state.hyperparameters;
+    let q = &td_state.q;
+    let mut g = bootstrap;
+    // The return as seen from the pair before the current one.
+    let mut backed_up = bootstrap;
+    for (state, action, sample) in buffer.iter().rev() {
+        g = sample.reward + backed_up;
+        backed_up = if tree_backup && *action != greedy(q, state) {
+            max_q(q, state)
+        } else {
+            g
+        };
+    }
+
+    let (state, action, _) = buffer[0];
+    td_state.q.update(&state, &action, |(v, n)| {
+        (v + h.step_size.at(*n) * (g - v), n + 1)
+    });
+
+    // Update V
+    let v = max_q(&td_state.q, &state);
+    td_state.v.update(&state, |(_, n)| (v, n + 1));
+}
+
+/// Double Q-learning keeps two independent estimates. Each update picks the best next
+/// action with one table and evaluates it with the other, which removes the
+/// overestimation that comes from maximising over noisy values. Actions are chosen with
+/// the average of both tables.
+pub struct DoubleQState<E = Easy21Env> {
+    pub v: V<(f64, i32), E>,
+    pub q1: Q<(f64, i32), E>,
+    pub q2: Q<(f64, i32), E>,
+    pub episodes: i32,
+    pub hyperparameters: Hyperparameters,
+}
+
+impl<E: Tabular> DoubleQState<E> {
+    pub fn init(hyperparameters: Hyperparameters) -> Self {
+        Self {
+            v: V::init((0.0, 0)),
+            q1: Q::init((0.0, 0)),
+            q2: Q::init((0.0, 0)),
+            episodes: 0,
+            hyperparameters,
+        }
+    }
+}
+
+impl<E: Tabular> HasQ<E> for DoubleQState<E> {
+    fn get_q(&self, state: &E::State, action: &E::Action) -> f64 {
+        (self.q1.get(state, action).0 + self.q2.get(state, action).0) / 2.0
+    }
+}
+
+impl<E: Tabular> Counted<E> for DoubleQState<E> {
+    fn count(&self, state: &E::State, action: &E::Action) -> i32 {
+        self.q1.get(state, action).1 + self.q2.get(state, action).1
+    }
+}
+
+impl HasV for DoubleQState {
+    fn get_v(&self, state: &State) -> f64 {
+        self.v.get(state).0
+    }
+}
+
+impl Easy21State for DoubleQState {
+    fn update(&mut self, env: &Easy21Env, rng: &mut StdRng) {
+        let exploration = self.hyperparameters.exploration;
+        double_q_learning(env, rng, &exploration, self);
+    }
+    fn episodes(&self) -> i32 {
+        self.episodes
+    }
+    fn policy(&self, state: &State) -> Action {
+        greedy(self, state)
+    }
+    fn rms_error(&self, reference: &dp::Reference) -> f64 {
+        reference.optimal.rms_error(self)
+    }
+    fn set_hyperparameters(&mut self, h: Hyperparameters) {
+        self.hyperparameters = h;
+    }
+    fn save(&self) -> persistence::Learner {
+        persistence::Learner::DoubleQLearning {
+            v: self.v.clone(),
+            q1: self.q1.clone(),
+            q2: self.q2.clone(),
+            episodes: self.episodes,
+            hyperparameters: self.hyperparameters,
+        }
+    }
+}
+
+pub fn double_q_learning<E: Tabular, R: Rng, X: Exploration<E>>(
+    env: &E,
+    rng: &mut R,
+    exploration: &X,
+    double_q_state: &mut DoubleQState<E>,
+) {
+    let h = double_q_state.hyperparameters;
+    let mut state = env.reset(rng);
+
+    loop {
+        let action = exploration.choose(rng, double_q_state, &state);
+        let sample = env.step(rng, state, action);
+
+        // Update one of the tables, chosen by a coin flip.
+        let DoubleQState { q1, q2, .. } = double_q_state;
+        let (update, evaluate) = if rng.gen::<bool>() {
+            (q1, q2)
+        } else {
+            (q2, q1)
+        };
+        let next_value = if sample.terminal {
+            0.0
+        } else {
+            let best = greedy(&*update, &sample.state);
+            evaluate.get(&sample.state, &best).0
+        };
+        update.update(&state, &action, |(v, n)| {
+            let td_error = sample.reward + next_value - v;
+            (v + h.step_size.at(*n) * td_error, n + 1)
+        });
+
+        // Update V
+        let v = max_q(double_q_state, &state);
+        double_q_state.v.update(&state, |(_, n)| (v, n + 1));
+
+        if sample.terminal {
+            break;
+        } else {
+            state = sample.state;
+        }
     }
+    double_q_state.episodes += 1;
 }
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Vector {
     w: Vec<f64>,
 }
 
 impl Vector {
-    fn init() -> Self {
-        let mut w = Vec::with_capacity(36);
-        w.resize(36, 0.0);
+    fn init(len: usize) -> Self {
+        let mut w = Vec::with_capacity(len);
+        w.resize(len, 0.0);
         Self { w }
     }
 
-    pub fn cuboid_features(state: &State, action: &Action) -> Self {
-        let mut result = Vec::with_capacity(36);
-        for dealer_interval in &[1..=4, 4..=7, 7..=10] {
-            for player_interval in &[1..=6, 4..=9, 7..=12, 10..=15, 13..=18, 16..=21] {
-                for a in &[Action::Hit, Action::Stick] {
-                    let in_dealer = dealer_interval.contains(&state.dealer);
-                    let in_player = player_interval.contains(&state.player);
-                    result.push(if in_dealer && in_player && action == a {
-                        1.0
-                    } else {
-                        0.0
-                    });
-                }
-            }
+    /// Adds `scale * x`, touching only the non-zero components of `x`.
+    fn add_sparse(&mut self, x: &SparseVector, scale: f64) {
+        for &(i, x) in &x.entries {
+            self.w[i] += scale * x;
         }
-        Self { w: result }
     }
 
-    fn get_q(&self, state: &State, action: &Action) -> f64 {
-        Self::cuboid_features(state, action)
-            .w
-            .iter()
-            .zip(&self.w)
-            .map(|(a, b)| a * b)
-            .sum()
+    /// The dot product with the traces, as a sparse vector.
+    fn dot_traces(&self, traces: &Traces) -> f64 {
+        traces.iter().map(|(i, e)| e * self.w[*i]).sum()
     }
 
-    fn zip_with<F>(&mut self, other: &Vector, f: F)
+    /// Combines every weight that has a trace in `traces` with its trace, in place.
+    fn zip_traces<F>(&mut self, traces: &Traces, f: F)
     where
         F: Fn(f64, f64) -> f64,
     {
-        self.w = self
-            .w
-            .iter()
-            .zip(&other.w)
-            .map(|(a, b)| f(*a, *b))
-            .collect();
+        for &(i, e) in traces.iter() {
+            self.w[i] = f(self.w[i], e);
+        }
     }
 }
 
-impl HasQ for Vector {
-    fn get_q(&self, state: &State, action: &Action) -> f64 {
-        self.get_q(state, action)
+/// The linear control algorithms that share [`ApproxState`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LinearControlMethod {
+    /// SARSA(λ) with the backward view of the traces.
+    Sarsa,
+    /// True online SARSA(λ).
+    TrueOnlineSarsa,
+}
+
+/// A vector given by its non-zero components, such as the features of a state-action
+/// pair where only a few features are active.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SparseVector {
+    /// Index and value of each non-zero component.
+    pub entries: Vec<(usize, f64)>,
+}
+
+impl SparseVector {
+    pub fn dot(&self, other: &Vector) -> f64 {
+        self.entries.iter().map(|(i, x)| x * other.w[*i]).sum()
     }
 }
 
-#[derive(Clone)]
-pub struct ApproxState {
+/// Linear action values: the dot product of the weights `q` with the features of a
+/// state-action pair.
+pub struct ApproxState<E: Environment = Easy21Env, F = Features> {
     pub q: Vector,
-    pub eligibility_traces: Vector,
+    pub eligibility_traces: Traces,
+    pub features: F,
     pub episodes: i32,
-    pub rms_error: f64,
+    pub method: LinearControlMethod,
+    pub hyperparameters: Hyperparameters,
+    pub environment: PhantomData<E>,
 }
 
-impl ApproxState {
-    pub fn init() -> Self {
+impl<E: Environment, F: FeatureExtractor<E>> ApproxState<E, F> {
+    /// Linear SARSA(λ) with one weight per feature. Set `method` for true online
+    /// SARSA(λ).
+    pub fn init(hyperparameters: Hyperparameters, features: F) -> Self {
+        let len = features.dimension();
         Self {
-            q: Vector::init(),
-            eligibility_traces: Vector::init(),
+            q: Vector::init(len),
+            eligibility_traces: Traces::default(),
+            features,
             episodes: 0,
-            rms_error: 0.0,
+            method: LinearControlMethod::Sarsa,
+            hyperparameters,
+            environment: PhantomData,
         }
     }
 }
 
 impl HasV for ApproxState {
     fn get_v(&self, state: &State) -> f64 {
-        let q_hit = self.q.get_q(state, &Action::Hit);
-        let q_stick = self.q.get_q(state, &Action::Stick);
-        q_hit.max(q_stick)
+        max_q(self, state)
+    }
+}
+
+impl<E: Environment, F: FeatureExtractor<E>> HasQ<E> for ApproxState<E, F> {
+    fn get_q(&self, state: &E::State, action: &E::Action) -> f64 {
+        self.features.value(state, action, &self.q)
     }
 }
 
-impl HasQ for ApproxState {
-    fn get_q(&self, state: &State, action: &Action) -> f64 {
-        self.q.get_q(state, action)
+/// Linear action values keep no counts, so every state looks unvisited.
+impl<E: Environment, F: FeatureExtractor<E>> Counted<E> for ApproxState<E, F> {
+    fn count(&self, _state: &E::State, _action: &E::Action) -> i32 {
+        0
     }
 }
 
 impl Easy21State for ApproxState {
-    fn update(&mut self, rng: &mut rand::prelude::ThreadRng) {
-        approx_td_lambda_control(rng, 0.1, self);
+    fn update(&mut self, env: &Easy21Env, rng: &mut StdRng) {
+        let exploration = self.hyperparameters.exploration;
+        match self.method {
+            LinearControlMethod::Sarsa => approx_td_lambda_control(env, rng, &exploration, self),
+            LinearControlMethod::TrueOnlineSarsa => {
+                true_online_sarsa_lambda(env, rng, &exploration, self)
+            }
+        }
     }
     fn episodes(&self) -> i32 {
         self.episodes
@@ -941,3445 +2676,682 @@ impl Easy21State for ApproxState {
             Action::Stick
         }
     }
-    fn rms_error(&self) -> f64 {
-        self.rms_error
+    fn rms_error(&self, reference: &dp::Reference) -> f64 {
+        reference.optimal.rms_error(self)
+    }
+    fn set_hyperparameters(&mut self, h: Hyperparameters) {
+        self.hyperparameters = h;
+    }
+    fn save(&self) -> persistence::Learner {
+        let weights = self.q.clone();
+        let episodes = self.episodes;
+        // The features in use, which differ from the settings after a change that waits
+        // for the next restart.
+        let hyperparameters = Hyperparameters {
+            features: self.features,
+            ..self.hyperparameters
+        };
+        match self.method {
+            LinearControlMethod::Sarsa => persistence::Learner::ApproxTDLambdaControl {
+                weights,
+                episodes,
+                hyperparameters,
+            },
+            LinearControlMethod::TrueOnlineSarsa => persistence::Learner::TrueOnlineSarsaLambda {
+                weights,
+                episodes,
+                hyperparameters,
+            },
+        }
     }
 }
 
-pub fn approx_td_lambda_control<R: Rng>(rng: &mut R, lambda: f64, approx_state: &mut ApproxState) {
-    approx_state.eligibility_traces = Vector::init();
-    let mut state = State::init(rng);
+pub fn approx_td_lambda_control<
+    E: Environment,
+    R: Rng,
+    X: Exploration<E>,
+    F: FeatureExtractor<E>,
+>(
+    env: &E,
+    rng: &mut R,
+    exploration: &X,
+    approx_state: &mut ApproxState<E, F>,
+) {
+    let h = approx_state.hyperparameters;
+    let alpha = h.step_size.at(approx_state.episodes);
+    approx_state.eligibility_traces.clear();
+    let mut state = env.reset(rng);
 
-    let eps = 0.05;
-    let mut action = epsilon_greedy(rng, eps, &approx_state.q, &state);
+    let mut action = exploration.choose(rng, approx_state, &state);
 
     loop {
-        let sample = step(rng, state, action);
-        let next_state = sample.state;
-
-        let next_action = epsilon_greedy(rng, eps, &approx_state.q, &next_state);
+        let sample = env.step(rng, state, action);
 
         // Update eligibility traces
+        let x = approx_state.features.extract(&state, &action);
+        h.trace
+            .update_linear(&mut approx_state.eligibility_traces, &x, h.lambda, alpha);
+
+        let (next_value, next) = if sample.terminal {
+            (0.0, None)
+        } else {
+            let next_state = sample.state;
+            let next_action = exploration.choose(rng, approx_state, &next_state);
+            (
+                approx_state.get_q(&next_state, &next_action),
+                Some((next_state, next_action)),
+            )
+        };
+
+        let td_error = sample.reward + next_value - approx_state.get_q(&state, &action);
         approx_state
-            .eligibility_traces
-            .zip_with(&Vector::cuboid_features(&state, &action), |e, x| {
-                lambda * e + x
+            .q
+            .zip_traces(&approx_state.eligibility_traces, |w, eligibility| {
+                w + alpha * td_error * eligibility
             });
 
-        let td_error = (sample.reward as f64) + approx_state.q.get_q(&next_state, &next_action)
-            - approx_state.q.get_q(&state, &action);
+        match next {
+            None => break,
+            Some((next_state, next_action)) => {
+                state = next_state;
+                action = next_action;
+            }
+        }
+    }
+    approx_state.episodes += 1;
+}
+
+/// True online SARSA(λ): with dutch traces and a correction term, every update equals
+/// that of the forward view of SARSA(λ) truncated at the current step.
+pub fn true_online_sarsa_lambda<
+    E: Environment,
+    R: Rng,
+    X: Exploration<E>,
+    F: FeatureExtractor<E>,
+>(
+    env: &E,
+    rng: &mut R,
+    exploration: &X,
+    approx_state: &mut ApproxState<E, F>,
+) {
+    let h = approx_state.hyperparameters;
+    let alpha = h.step_size.at(approx_state.episodes);
+    approx_state.eligibility_traces.clear();
+    let mut state = env.reset(rng);
+
+    let mut action = exploration.choose(rng, approx_state, &state);
+    let mut x = approx_state.features.extract(&state, &action);
+    // Value of the current pair under the weights of the previous step.
+    let mut old_value = 0.0;
+
+    loop {
+        let sample = env.step(rng, state, action);
+        let value = x.dot(&approx_state.q);
+
+        let (next_value, next) = if sample.terminal {
+            (0.0, None)
+        } else {
+            let next_state = sample.state;
+            let next_action = exploration.choose(rng, approx_state, &next_state);
+            let next_x = approx_state.features.extract(&next_state, &next_action);
+            (
+                next_x.dot(&approx_state.q),
+                Some((next_state, next_action, next_x)),
+            )
+        };
+
+        let td_error = sample.reward + next_value - value;
+        Trace::Dutch.update_linear(&mut approx_state.eligibility_traces, &x, h.lambda, alpha);
+        let correction = value - old_value;
         approx_state
             .q
-            .zip_with(&approx_state.eligibility_traces, |w, eligibility| {
-                let alpha = 0.0001;
+            .zip_traces(&approx_state.eligibility_traces, |w, eligibility| {
+                w + alpha * (td_error + correction) * eligibility
+            });
+        approx_state.q.add_sparse(&x, -alpha * correction);
+
+        match next {
+            None => break,
+            Some((next_state, next_action, next_x)) => {
+                old_value = next_value;
+                state = next_state;
+                action = next_action;
+                x = next_x;
+            }
+        }
+    }
+    approx_state.episodes += 1;
+}
+
+/// The linear prediction algorithms that share [`ApproxPredictionState`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LinearPredictionMethod {
+    /// Gradient Monte Carlo: moves the value of every visited state towards the return.
+    GradientMonteCarlo,
+    /// Semi-gradient TD(λ) with the backward view of the traces.
+    TDLambda,
+    /// Semi-gradient TD(λ) from the actions of a behaviour policy, with importance
+    /// sampling ratios in the traces. Can diverge.
+    OffPolicyTDLambda,
+    /// Off-policy gradient-TD: gradient descent on the projected Bellman error, with
+    /// secondary weights that estimate the expected TD error of each state.
+    GTD2,
+    /// Off-policy gradient-TD: the TD(λ) update with a correction from the secondary
+    /// weights.
+    TDC,
+}
+
+/// Linear state values of a fixed policy: the dot product of the weights `v` with the
+/// features of a state.
+pub struct ApproxPredictionState<E: Environment = Easy21Env, F = Features> {
+    pub v: Vector,
+    /// Secondary weights of the gradient-TD methods.
+    pub secondary: Vector,
+    pub eligibility_traces: Traces,
+    pub features: F,
+    pub episodes: i32,
+    pub method: LinearPredictionMethod,
+    pub policy: fn(&mut StdRng, &E::State) -> E::Action,
+    pub hyperparameters: Hyperparameters,
+}
+
+impl<E: Environment, F: StateFeatureExtractor<E>> ApproxPredictionState<E, F> {
+    /// Semi-gradient TD(λ) with one weight per state feature. Set `method` for gradient
+    /// Monte Carlo.
+    pub fn init(
+        hyperparameters: Hyperparameters,
+        features: F,
+        policy: fn(&mut StdRng, &E::State) -> E::Action,
+    ) -> Self {
+        let len = features.state_dimension();
+        Self {
+            v: Vector::init(len),
+            secondary: Vector::init(len),
+            eligibility_traces: Traces::default(),
+            features,
+            episodes: 0,
+            method: LinearPredictionMethod::TDLambda,
+            policy,
+            hyperparameters,
+        }
+    }
+
+    pub fn value(&self, state: &E::State) -> f64 {
+        self.features.state_value(state, &self.v)
+    }
+}
+
+impl HasV for ApproxPredictionState {
+    fn get_v(&self, state: &State) -> f64 {
+        self.value(state)
+    }
+}
+
+impl Easy21State for ApproxPredictionState {
+    fn update(&mut self, env: &Easy21Env, rng: &mut StdRng) {
+        match self.method {
+            LinearPredictionMethod::GradientMonteCarlo => {
+                gradient_monte_carlo_prediction(env, rng, self.policy, self)
+            }
+            LinearPredictionMethod::TDLambda => {
+                approx_td_lambda_prediction(env, rng, self.policy, self)
+            }
+            LinearPredictionMethod::OffPolicyTDLambda
+            | LinearPredictionMethod::GTD2
+            | LinearPredictionMethod::TDC => {
+                let exploration = self.hyperparameters.exploration;
+                off_policy_td_prediction(env, rng, &exploration, self.policy, self)
+            }
+        }
+    }
+    fn episodes(&self) -> i32 {
+        self.episodes
+    }
+    fn policy(&self, state: &State) -> Action {
+        // Fixed seed, so that a stochastic policy doesn't flicker in the plot.
+        (self.policy)(&mut StdRng::seed_from_u64(0), state)
+    }
+    fn rms_error(&self, reference: &dp::Reference) -> f64 {
+        reference.example_policy.rms_error_v(self)
+    }
+    fn set_hyperparameters(&mut self, h: Hyperparameters) {
+        self.hyperparameters = h;
+    }
+    fn save(&self) -> persistence::Learner {
+        let weights = self.v.clone();
+        let episodes = self.episodes;
+        let hyperparameters = Hyperparameters {
+            features: self.features,
+            ..self.hyperparameters
+        };
+        match self.method {
+            LinearPredictionMethod::GradientMonteCarlo => {
+                persistence::Learner::GradientMonteCarloPrediction {
+                    weights,
+                    episodes,
+                    hyperparameters,
+                }
+            }
+            LinearPredictionMethod::TDLambda => persistence::Learner::ApproxTDLambdaPrediction {
+                weights,
+                episodes,
+                hyperparameters,
+            },
+            LinearPredictionMethod::OffPolicyTDLambda => {
+                persistence::Learner::OffPolicyTDLambdaPrediction {
+                    weights,
+                    episodes,
+                    hyperparameters,
+                }
+            }
+            LinearPredictionMethod::GTD2 => persistence::Learner::GTD2Prediction {
+                weights,
+                secondary_weights: self.secondary.clone(),
+                episodes,
+                hyperparameters,
+            },
+            LinearPredictionMethod::TDC => persistence::Learner::TDCPrediction {
+                weights,
+                secondary_weights: self.secondary.clone(),
+                episodes,
+                hyperparameters,
+            },
+        }
+    }
+}
+
+/// One episode of gradient Monte Carlo prediction: after the episode, the value of every
+/// visited state moves towards the final reward along its features.
+pub fn gradient_monte_carlo_prediction<
+    E: Environment,
+    R: Rng,
+    P: Fn(&mut R, &E::State) -> E::Action,
+    F: StateFeatureExtractor<E>,
+>(
+    env: &E,
+    rng: &mut R,
+    policy: P,
+    approx_state: &mut ApproxPredictionState<E, F>,
+) {
+    let alpha = approx_state
+        .hyperparameters
+        .step_size
+        .at(approx_state.episodes);
+    let (state_actions, reward) = episode(env, rng, policy);
+    for (state, _) in state_actions {
+        let x = approx_state.features.extract_state(&state);
+        let error = reward - x.dot(&approx_state.v);
+        approx_state.v.add_sparse(&x, alpha * error);
+    }
+    approx_state.episodes += 1;
+}
+
+/// One episode of semi-gradient TD(λ): like tabular TD(λ), with traces over the
+/// features instead of the states.
+pub fn approx_td_lambda_prediction<
+    E: Environment,
+    R: Rng,
+    P: Fn(&mut R, &E::State) -> E::Action,
+    F: StateFeatureExtractor<E>,
+>(
+    env: &E,
+    rng: &mut R,
+    policy: P,
+    approx_state: &mut ApproxPredictionState<E, F>,
+) {
+    let h = approx_state.hyperparameters;
+    let alpha = h.step_size.at(approx_state.episodes);
+    approx_state.eligibility_traces.clear();
+    let mut state = env.reset(rng);
+    loop {
+        let action = policy(rng, &state);
+        let sample = env.step(rng, state, action);
+
+        // Update eligibility traces
+        let x = approx_state.features.extract_state(&state);
+        h.trace
+            .update_linear(&mut approx_state.eligibility_traces, &x, h.lambda, alpha);
+
+        let next_value = if sample.terminal {
+            0.0
+        } else {
+            approx_state.value(&sample.state)
+        };
+        let td_error = sample.reward + next_value - approx_state.value(&state);
+        approx_state
+            .v
+            .zip_traces(&approx_state.eligibility_traces, |w, eligibility| {
                 w + alpha * td_error * eligibility
             });
 
         if sample.terminal {
             break;
         } else {
-            state = next_state;
-            action = next_action;
+            state = sample.state;
         }
     }
     approx_state.episodes += 1;
-    if approx_state.episodes % 1000 == 0 {
-        approx_state.rms_error = 0.0 // approx_state.q.rms_error();
+}
+
+/// One episode of off-policy linear prediction of the deterministic policy `target`,
+/// following a behaviour policy that explores around it. The method of `approx_state`
+/// picks between semi-gradient TD(λ), GTD2(λ) and TDC(λ). All three decay their traces
+/// by the importance sampling ratio, so that the traces vanish after actions the target
+/// policy doesn't take.
+pub fn off_policy_td_prediction<
+    E: Environment,
+    R: Rng,
+    X: Exploration<E>,
+    P: Fn(&mut R, &E::State) -> E::Action,
+    F: StateFeatureExtractor<E>,
+>(
+    env: &E,
+    rng: &mut R,
+    exploration: &X,
+    target: P,
+    approx_state: &mut ApproxPredictionState<E, F>,
+) {
+    let h = approx_state.hyperparameters;
+    let alpha = h.step_size.at(approx_state.episodes);
+    let beta = h.secondary_step_size * alpha;
+    approx_state.eligibility_traces.clear();
+    let mut state = env.reset(rng);
+    loop {
+        let values = TargetValues::<E>(target(rng, &state));
+        let action = exploration.choose(rng, &values, &state);
+        let rho = values.get_q(&state, &action) / exploration.probability(&values, &state, &action);
+        let sample = env.step(rng, state, action);
+
+        // Update eligibility traces
+        let x = approx_state.features.extract_state(&state);
+        Trace::Accumulating.update_linear(
+            &mut approx_state.eligibility_traces,
+            &x,
+            h.lambda,
+            alpha,
+        );
+        approx_state.eligibility_traces.decay(rho);
+
+        let next_x = if sample.terminal {
+            SparseVector::default()
+        } else {
+            approx_state.features.extract_state(&sample.state)
+        };
+        let td_error = sample.reward + next_x.dot(&approx_state.v) - x.dot(&approx_state.v);
+        let traces = &approx_state.eligibility_traces;
+        // Both read before the secondary weights move.
+        let traced_secondary = approx_state.secondary.dot_traces(traces);
+        let secondary_value = x.dot(&approx_state.secondary);
+        match approx_state.method {
+            LinearPredictionMethod::GTD2 => {
+                approx_state.v.add_sparse(&x, alpha * secondary_value);
+            }
+            _ => {
+                approx_state
+                    .v
+                    .zip_traces(traces, |w, eligibility| w + alpha * td_error * eligibility);
+            }
+        }
+        if approx_state.method != LinearPredictionMethod::OffPolicyTDLambda {
+            let correction = alpha * (1.0 - h.lambda) * traced_secondary;
+            approx_state.v.add_sparse(&next_x, -correction);
+            approx_state
+                .secondary
+                .zip_traces(traces, |w, eligibility| w + beta * td_error * eligibility);
+            approx_state
+                .secondary
+                .add_sparse(&x, -beta * secondary_value);
+        }
+
+        if sample.terminal {
+            break;
+        } else {
+            state = sample.state;
+        }
     }
+    approx_state.episodes += 1;
 }
 
 impl Q<(f64, i32)> {
-    fn rms_error(&self) -> f64 {
-        let optimal = vec![
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            -0.11491226520118117,
-            -0.06786737907363498,
-            -0.030619839104085844,
-            0.02775868450272315,
-            0.061262189381693796,
-            0.10621665874399272,
-            0.1555898930659694,
-            0.2121437686091034,
-            0.2802836928031776,
-            0.35233777664983607,
-            0.42925827494563323,
-            0.34407244146623917,
-            0.2398061694835128,
-            0.18254739912493953,
-            0.06708229426433908,
-            -0.009306365554038989,
-            -0.10963455149501682,
-            -0.193317732709308,
-            -0.297388716005953,
-            -0.4092653871608203,
-            -0.5230769230769236,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            -0.15451339915373635,
-            -0.1137133887481722,
-            -0.0705105633802819,
-            -0.025558656766198024,
-            0.012102283818075186,
-            0.0543795880550208,
-            0.1076041187502205,
-            0.1536284546312137,
-            0.22808896066281567,
-            0.30187863383590896,
-            0.38025125355902867,
-            0.2966944943840231,
-            0.20942916010180543,
-            0.12076496281430746,
-            0.032867910539972706,
-            -0.04432937610507716,
-            -0.13295370141124052,
-            -0.22555893636724395,
-            -0.32519043866561637,
-            -0.43216896831843926,
-            -0.5516467065868265,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            -0.19536358938721568,
-            -0.14654337296345368,
-            -0.10503668171557536,
-            -0.06577327564981808,
-            -0.029844694766160685,
-            0.013401313576575107,
-            0.05375605460149762,
-            0.11313887863283897,
-            0.18295655834020405,
-            0.25710431103823017,
-            0.3314964501583053,
-            0.2528369643284729,
-            0.1905268245529248,
-            0.08493743984600566,
-            0.014823761941363759,
-            -0.049426301853486,
-            -0.1464570858283433,
-            -0.25029469548133604,
-            -0.3437168610816545,
-            -0.44753511429358345,
-            -0.5609386828160483,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            -0.21008193801211164,
-            -0.17538001933046252,
-            -0.14231569956760762,
-            -0.10002115357495371,
-            -0.06990163701573071,
-            -0.021642909420415836,
-            0.015474564996674099,
-            0.07142732370255199,
-            0.14453983168283382,
-            0.21653032706435663,
-            0.2938813980573196,
-            0.21884597985762266,
-            0.1464528213533124,
-            0.0506744440393731,
-            -0.01988047322844251,
-            -0.06458797327394196,
-            -0.1585381222432264,
-            -0.25984354628422407,
-            -0.3548343657419879,
-            -0.4483735996760701,
-            -0.5844961240310086,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            -0.2435895170268789,
-            -0.20645002375213387,
-            -0.1739972532309745,
-            -0.1369076697606272,
-            -0.09930191972076824,
-            -0.05834504567814508,
-            -0.017246511302155376,
-            0.03611378555798728,
-            0.10549587198435352,
-            0.18136593115015046,
-            0.25978829599981196,
-            0.1838975950644522,
-            0.1149926675945047,
-            0.04564315352697091,
-            -0.021732858928743163,
-            -0.09004133044676231,
-            -0.18575572671801516,
-            -0.27386587771203197,
-            -0.37329255861365934,
-            -0.47527891955372975,
-            -0.5744125326370759,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            -0.27852384108270123,
-            -0.22820869565217436,
-            -0.20029916165165054,
-            -0.16349691661916432,
-            -0.12039571710731506,
-            -0.09318406322028257,
-            -0.04723023010927184,
-            0.0019072140370952945,
-            0.07787374166557515,
-            0.15049777100223782,
-            0.2322769894004378,
-            0.1591973727771936,
-            0.08931224901653322,
-            0.022931830633169035,
-            -0.0655301845480141,
-            -0.11994868505452196,
-            -0.18766108247422658,
-            -0.28786023348844725,
-            -0.3706597921533877,
-            -0.48783783783783885,
-            -0.6012031139419687,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            -0.33104459587682467,
-            -0.2841134384966806,
-            -0.24320319768443688,
-            -0.22101860449121533,
-            -0.17552937972356536,
-            -0.1597104283533206,
-            -0.10790774299835315,
-            -0.05206672495671124,
-            0.019142135443477693,
-            0.0961110218802743,
-            0.1794773445607034,
-            0.11318514725009655,
-            0.046558623334840905,
-            -0.016420994295421824,
-            -0.09542743538767391,
-            -0.1453172205438066,
-            -0.22816421001340034,
-            -0.32323987538940846,
-            -0.4060579728136082,
-            -0.5018618506795725,
-            -0.6012630662020908,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            -0.38128457604119714,
-            -0.34740869685114084,
-            -0.30124530876833583,
-            -0.2748016042324442,
-            -0.24459988712353048,
-            -0.21574503793262761,
-            -0.17731857235032436,
-            -0.12170442501324903,
-            -0.044085019132882305,
-            0.03507587566000208,
-            0.12112500147282204,
-            0.05802993848060112,
-            -0.0019452718850165546,
-            -0.06202731559747433,
-            -0.11872603490100896,
-            -0.17585015457355768,
-            -0.24410207029369296,
-            -0.3300396462336063,
-            -0.4195273631840809,
-            -0.5112491000719918,
-            -0.6316855753646693,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            -0.4412216345333551,
-            -0.39317547341159326,
-            -0.3555092507015711,
-            -0.32415521476465725,
-            -0.29958776881943167,
-            -0.2788138632584532,
-            -0.2358623199192821,
-            -0.18023687920556775,
-            -0.1100223593361758,
-            -0.029017707801376656,
-            0.05740426948968794,
-            0.00035883193030051847,
-            -0.05629316779741276,
-            -0.11026174001039385,
-            -0.16158840742187114,
-            -0.21309033901626462,
-            -0.2780706585884008,
-            -0.35184598320392957,
-            -0.44065742267041713,
-            -0.5268760678144307,
-            -0.6365723029839313,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            -0.4876375952582563,
-            -0.4490466909583913,
-            -0.41903708915468835,
-            -0.38586253771426776,
-            -0.3609585290164124,
-            -0.3383834548400181,
-            -0.29713587779121853,
-            -0.247434215378843,
-            -0.18026142931930386,
-            -0.10015519728161736,
-            -0.010927569586099766,
-            -0.06498681033376622,
-            -0.11433335009236398,
-            -0.16548244144635044,
-            -0.2113559410967657,
-            -0.2591830025255648,
-            -0.3114327592524731,
-            -0.3858151854031789,
-            -0.4597217873596612,
-            -0.5684354361193283,
-            -0.6447971781305095,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.3168706937083459,
-            0.31727155110871075,
-            0.3171743578086117,
-            0.3175906235606465,
-            0.3171138960758811,
-            0.31783995656555397,
-            0.3174790858326245,
-            0.31594987619235393,
-            0.31709862805375544,
-            0.32060013654605163,
-            0.3233732559575261,
-            0.3164049983979489,
-            0.32208054306944117,
-            0.3165370149741887,
-            0.32078045308979136,
-            0.31507517467594726,
-            0.4063164174652234,
-            0.5769814061962328,
-            0.7222632752540836,
-            0.8497623283851594,
-            0.9535849810265585,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.25662148322602873,
-            0.25524284089066335,
-            0.2557964717491214,
-            0.255786602950473,
-            0.25545180177758176,
-            0.25485154366743284,
-            0.25585445486222624,
-            0.2559006702019785,
-            0.25488714372870336,
-            0.2509306717021849,
-            0.25752343364578245,
-            0.24248642124321151,
-            0.2521696207181575,
-            0.2551437695963584,
-            0.2564587271581569,
-            0.2547692943084328,
-            0.35160616252039495,
-            0.5328122435063714,
-            0.6971528798048026,
-            0.8329829015749606,
-            0.9489739102768059,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.20044513271503547,
-            0.20111479841889957,
-            0.1991708483612874,
-            0.20144575148794047,
-            0.2010188488005519,
-            0.20137733540680147,
-            0.20186688204607922,
-            0.19982519904093596,
-            0.20145460358835873,
-            0.2010686164229486,
-            0.20215701028342117,
-            0.21324717285945002,
-            0.1999008993956,
-            0.20211266369927114,
-            0.2006775940638971,
-            0.20160160401971455,
-            0.30703995852312815,
-            0.4936511247211132,
-            0.6683448182503812,
-            0.8154756011432122,
-            0.9422406967537619,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.1510017796404597,
-            0.1528746493357407,
-            0.15147015656416285,
-            0.15308361465508738,
-            0.15221660890167574,
-            0.14996200452038325,
-            0.15196657803894836,
-            0.15260705525880822,
-            0.15206524611641387,
-            0.15032656411720555,
-            0.15016282716198395,
-            0.15667936727860468,
-            0.15147767031431406,
-            0.1504201827693027,
-            0.14973573252568334,
-            0.15166264097488508,
-            0.2602925848719479,
-            0.4610191967668689,
-            0.6427047084382823,
-            0.7998067939709153,
-            0.9353223948194271,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.10748442229990916,
-            0.10741130673487607,
-            0.10657116440721406,
-            0.10783988215456847,
-            0.10765978875241491,
-            0.10641063686568766,
-            0.10751215454213317,
-            0.10765242570034726,
-            0.1069262412525317,
-            0.11110717197858636,
-            0.1197799959258502,
-            0.08450289527922275,
-            0.09508389755666777,
-            0.10776001437039734,
-            0.10693205591588073,
-            0.10564278976862908,
-            0.2208740027110332,
-            0.4271774208948383,
-            0.6206821438252732,
-            0.7875788149917154,
-            0.9329004329004278,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0698649899955982,
-            0.06886966805001381,
-            0.07019714127735399,
-            0.06783967377704865,
-            0.07080871096615295,
-            0.06981813545549646,
-            0.06982330448476157,
-            0.07055785767140832,
-            0.06864684865321226,
-            0.0717938820804786,
-            0.05797450084195352,
-            0.0746526414946229,
-            0.06520588546628582,
-            0.07070743067706722,
-            0.06686082224647191,
-            0.06765248998241143,
-            0.18546989557876417,
-            0.39835483689222234,
-            0.5999581470560069,
-            0.7784472407535921,
-            0.9276614600642554,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            -0.0256333644923194,
-            -0.026354378549853456,
-            -0.026687781999803477,
-            -0.026163663489201194,
-            -0.026374458849310816,
-            -0.02653535049884668,
-            -0.02647140944926371,
-            -0.025400634007980597,
-            -0.027159119029943068,
-            -0.02589552495105952,
-            -0.03620985859653759,
-            -0.03683702989392465,
-            -0.027232580961727156,
-            -0.01923302176306596,
-            -0.026551641504912193,
-            -0.029370306429570545,
-            0.14770427172101724,
-            0.4209621006401787,
-            0.6115667037412217,
-            0.7822005796495902,
-            0.9301553909714897,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            -0.11967703153876463,
-            -0.11956321443635208,
-            -0.11946264609437775,
-            -0.11991061581298706,
-            -0.1186146148267004,
-            -0.12068032104538218,
-            -0.11898122827346212,
-            -0.11904083273663528,
-            -0.12295852630845804,
-            -0.12242428374209682,
-            -0.11212121212121211,
-            -0.1206382150732907,
-            -0.11724680874992924,
-            -0.10383584244174139,
-            -0.11904769083722135,
-            -0.12043948515947298,
-            0.05136777061260892,
-            0.3762041093089053,
-            0.6242304864384036,
-            0.7883013693963756,
-            0.933783231083846,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            -0.2096854114217828,
-            -0.2100423515543988,
-            -0.20924228618391802,
-            -0.21108751817782284,
-            -0.20928887424369574,
-            -0.2098251628415254,
-            -0.20983648651245793,
-            -0.20268521514073087,
-            -0.20699017007333353,
-            -0.20977066260084862,
-            -0.20101464109296496,
-            -0.20890202555854945,
-            -0.22059894606057945,
-            -0.2165551839464881,
-            -0.2176614881439092,
-            -0.2083310310858574,
-            -0.04656015195549268,
-            0.2691673076247886,
-            0.5759294460990402,
-            0.7957073831830868,
-            0.9345634482649406,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            -0.29650053704427826,
-            -0.29702139861645266,
-            -0.2970738847754726,
-            -0.29684227184111633,
-            -0.295908233579969,
-            -0.29716210543131866,
-            -0.29687932234649017,
-            -0.2971028442177169,
-            -0.2949186735594533,
-            -0.2979404887735794,
-            -0.28819212808539174,
-            -0.29614884627884625,
-            -0.2999677106877632,
-            -0.2824929178470267,
-            -0.29098185491704526,
-            -0.29026829952322786,
-            -0.1382409968349895,
-            0.16811610130521104,
-            0.46322344067645704,
-            0.7391787683281347,
-            0.9380040446383967,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-        ];
-        self.0
-            .iter()
-            .zip(optimal)
-            .map(|((v, _), v_star)| {
-                let diff = v - v_star;
-                diff * diff
-            })
-            .sum()
+    /// Root mean squared difference to the reference action values over all
+    /// non-terminal state-action pairs.
+    fn rms_error(&self, reference: &dp::Solution) -> f64 {
+        reference.rms_error(self)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::env::Corridor;
+
+    #[test]
+    fn test_td_lambda_control_on_other_environment() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut td_state = TDControlState::<Corridor<3>>::init(Hyperparameters::default());
+        for _ in 0..1000 {
+            td_lambda_control(&Corridor, &mut rng, &Strategy::default(), &mut td_state);
+        }
+        for state in 0..3 {
+            assert!(greedy::<Corridor<3>, _>(&td_state.q, &state));
+        }
+    }
+
+    #[test]
+    fn test_td_control_methods_on_other_environment() {
+        let methods = [
+            TDControlMethod::QLearning,
+            TDControlMethod::WatkinsQLambda,
+            TDControlMethod::ExpectedSarsa,
+            TDControlMethod::NStepSarsa,
+            TDControlMethod::NStepTreeBackup,
+        ];
+        for method in methods {
+            let mut rng = StdRng::seed_from_u64(0);
+            let mut td_state = TDControlState::<Corridor<3>> {
+                method,
+                ..TDControlState::init(Hyperparameters::default())
+            };
+            for _ in 0..1000 {
+                td_control(&Corridor, &mut rng, &Strategy::default(), &mut td_state);
+            }
+            for state in 0..3 {
+                assert!(
+                    greedy::<Corridor<3>, _>(&td_state.q, &state),
+                    "{:?}",
+                    method
+                );
+            }
+        }
+
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut double_q_state = DoubleQState::<Corridor<3>>::init(Hyperparameters::default());
+        for _ in 0..1000 {
+            double_q_learning(
+                &Corridor,
+                &mut rng,
+                &Strategy::default(),
+                &mut double_q_state,
+            );
+        }
+        for state in 0..3 {
+            assert!(greedy::<Corridor<3>, _>(&double_q_state, &state));
+        }
+    }
+
+    #[test]
+    fn test_trace_variants_on_other_environment() {
+        for trace in [Trace::Accumulating, Trace::Replacing, Trace::Dutch] {
+            let h = Hyperparameters {
+                trace,
+                ..Default::default()
+            };
+            let mut rng = StdRng::seed_from_u64(0);
+            let mut td_state = TDControlState::<Corridor<3>>::init(h);
+            for _ in 0..1000 {
+                td_lambda_control(&Corridor, &mut rng, &Strategy::default(), &mut td_state);
+            }
+            for state in 0..3 {
+                assert!(greedy::<Corridor<3>, _>(&td_state.q, &state), "{:?}", trace);
+            }
+        }
+
+        let h = Algorithm::TrueOnlineSarsaLambda.hyperparameters();
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut approx_state = ApproxState::<Corridor<3>, _>::init(
+            Hyperparameters {
+                step_size: StepSize::Constant(0.1),
+                ..h
+            },
+            features::OneHot,
+        );
+        for _ in 0..1000 {
+            true_online_sarsa_lambda(&Corridor, &mut rng, &h.exploration, &mut approx_state);
+        }
+        for state in 0..3 {
+            assert!(greedy::<Corridor<3>, _>(&approx_state, &state));
+        }
+    }
+
+    #[test]
+    fn test_one_hot_features_match_tabular() {
+        let env = Easy21Env::default();
+        let h = Hyperparameters {
+            step_size: StepSize::Constant(0.05),
+            exploration: Strategy::EpsilonGreedy(Schedule::Constant(0.1)),
+            ..Default::default()
+        };
+        let mut td_state = TDControlState::init(h);
+        let mut approx_state = ApproxState::init(h, features::OneHot);
+        let mut td_rng = StdRng::seed_from_u64(0);
+        let mut approx_rng = StdRng::seed_from_u64(0);
+        for _ in 0..1000 {
+            td_lambda_control(&env, &mut td_rng, &h.exploration, &mut td_state);
+            approx_td_lambda_control(&env, &mut approx_rng, &h.exploration, &mut approx_state);
+        }
+        let weights: Vec<f64> = td_state.q.0.iter().map(|(v, _)| *v).collect();
+        assert!(weights == approx_state.q.w);
+    }
+
+    #[test]
+    fn test_linear_prediction() {
+        let env = Easy21Env::default();
+        let h = Hyperparameters {
+            step_size: StepSize::Constant(0.05),
+            ..Default::default()
+        };
+        let mut td_state = TDState::init(h, example_policy);
+        let mut approx_state = ApproxPredictionState::init(h, features::OneHot, example_policy);
+        let mut td_rng = StdRng::seed_from_u64(0);
+        let mut approx_rng = StdRng::seed_from_u64(0);
+        for _ in 0..1000 {
+            td_lambda_prediction(&env, &mut td_rng, example_policy, &mut td_state);
+            approx_td_lambda_prediction(&env, &mut approx_rng, example_policy, &mut approx_state);
+        }
+        let weights: Vec<f64> = td_state.v.0.iter().map(|(v, _)| *v).collect();
+        assert!(weights == approx_state.v.w);
+
+        // The coarse cuboids can't follow the jump of V^π at a player sum of 20, while
+        // finer tiles get close.
+        let reference = dp::Reference::new(&env.rules);
+        let h = Algorithm::GradientMonteCarloPrediction.hyperparameters();
+        let tile_coding = Features::TileCoding(features::TileCoding {
+            tilings: 8,
+            tiles: 4,
+        });
+        let error = |features| {
+            let mut mc_state = ApproxPredictionState {
+                method: LinearPredictionMethod::GradientMonteCarlo,
+                ..ApproxPredictionState::init(h, features, example_policy)
+            };
+            let mut rng = StdRng::seed_from_u64(0);
+            for _ in 0..100_000 {
+                mc_state.update(&env, &mut rng);
+            }
+            mc_state.rms_error(&reference)
+        };
+        let cuboid_error = error(Features::Cuboid);
+        let tile_error = error(tile_coding);
+        assert!(cuboid_error > 0.2, "{}", cuboid_error);
+        assert!(tile_error < 0.15, "{}", tile_error);
+    }
+
+    #[test]
+    fn test_one_step_special_cases() {
+        let env = Easy21Env::default();
+        let h = Hyperparameters {
+            lambda: 0.0,
+            n: 1,
+            ..Default::default()
+        };
+        let run = |method| {
+            let mut rng = StdRng::seed_from_u64(0);
+            let mut td_state = TDControlState {
+                method,
+                ..TDControlState::init(h)
+            };
+            for _ in 0..1000 {
+                td_control(&env, &mut rng, &h.exploration, &mut td_state);
+            }
+            td_state.q.0
+        };
+        assert!(run(TDControlMethod::NStepSarsa) == run(TDControlMethod::Sarsa));
+        assert!(run(TDControlMethod::NStepTreeBackup) == run(TDControlMethod::QLearning));
+    }
+
+    #[test]
+    fn test_same_seed_same_q() {
+        let env = Easy21Env::default();
+        let run = |seed| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let h = Hyperparameters::default();
+            let mut mc_state = MCControlState::init(h);
+            let mut td_state = TDControlState::init(h);
+            for _ in 0..1000 {
+                monte_carlo_control(&env, &mut rng, &h.exploration, &mut mc_state);
+                td_lambda_control(&env, &mut rng, &h.exploration, &mut td_state);
+            }
+            (mc_state.q.0, td_state.q.0)
+        };
+        assert!(run(1) == run(1));
+        assert!(run(1) != run(2));
+    }
+
+    #[test]
+    fn test_off_policy_prediction() {
+        let env = Easy21Env::default();
+        let reference = dp::Reference::new(&env.rules);
+        for sampling in [Sampling::Ordinary, Sampling::Weighted] {
+            let mut rng = StdRng::seed_from_u64(0);
+            let h = Algorithm::OffPolicyMCPrediction(sampling).hyperparameters();
+            let mut mc_state = OffPolicyMCState::init(h, sampling, Some(example_policy));
+            for _ in 0..100_000 {
+                off_policy_monte_carlo_prediction(
+                    &env,
+                    &mut rng,
+                    &h.exploration,
+                    example_policy,
+                    &mut mc_state,
+                );
+            }
+            let error = mc_state.rms_error(&reference);
+            assert!(error < 0.1, "{:?}: {}", sampling, error);
+        }
+    }
 
     #[test]
     fn test_cuboid_features() {
+        let x = features::Cuboid.extract(
+            &State {
+                player: 4,
+                dealer: 4,
+            },
+            &Action::Stick,
+        );
+        let mut dense = Vector::init(36);
+        dense.add_sparse(&x, 1.0);
         assert_eq!(
-            Vector::cuboid_features(
-                &State {
-                    player: 4,
-                    dealer: 4
-                },
-                &Action::Stick
-            )
-            .w,
+            dense.w,
             vec![
                 0.0, 1.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 1.0,
                 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
@@ -4388,3 +3360,117 @@ mod tests {
         )
     }
 }
+
+#[cfg(test)]
+mod benchmarks {
+    use super::*;
+
+    extern crate test;
+    use test::{black_box, Bencher};
+
+    /// Episodes per iteration, so that the traces span many episodes of a learner that
+    /// has seen most states.
+    const EPISODES: usize = 100;
+
+    #[bench]
+    fn b_td_lambda_control(b: &mut Bencher) {
+        let env = Easy21Env::default();
+        let mut rng = StdRng::seed_from_u64(0);
+        let h = Hyperparameters {
+            lambda: 0.9,
+            ..Hyperparameters::default()
+        };
+        let mut td_state = TDControlState::init(h);
+        b.iter(|| {
+            for _ in 0..EPISODES {
+                td_lambda_control(&env, &mut rng, &h.exploration, &mut td_state);
+            }
+            black_box(td_state.episodes)
+        });
+    }
+
+    /// SARSA(λ) as [`td_lambda_control`], but with a trace for every entry of the table
+    /// that is decayed and applied at every step, as a baseline for [`Traces`].
+    fn td_lambda_control_dense<R: Rng>(
+        env: &Easy21Env,
+        rng: &mut R,
+        exploration: &Strategy,
+        td_state: &mut TDControlState,
+        traces: &mut [f64],
+    ) {
+        let h = td_state.hyperparameters;
+        traces.fill(0.0);
+        let mut state = env.reset(rng);
+        let mut action = exploration.choose(rng, &td_state.q, &state);
+        loop {
+            let sample = env.step(rng, state, action);
+            for e in traces.iter_mut() {
+                *e *= h.lambda;
+            }
+            let i = Q::<f64>::index(&state, &action);
+            let alpha = h.step_size.at(td_state.q.get(&state, &action).1);
+            traces[i] = h.trace.visit(traces[i], alpha);
+
+            let (next_value, next) = if sample.terminal {
+                (0.0, None)
+            } else {
+                let next_action = exploration.choose(rng, &td_state.q, &sample.state);
+                (
+                    td_state.q.get(&sample.state, &next_action).0,
+                    Some((sample.state, next_action)),
+                )
+            };
+            let td_error = sample.reward + next_value - td_state.q.get(&state, &action).0;
+            for ((v, n), e) in td_state.q.0.iter_mut().zip(traces.iter()) {
+                *v += h.step_size.at(*n) * td_error * e;
+            }
+            td_state.q.update(&state, &action, |(v, n)| (*v, *n + 1));
+            let v = max_q(&td_state.q, &state);
+            td_state.v.update(&state, |(_, n)| (v, n + 1));
+
+            match next {
+                None => break,
+                Some((next_state, next_action)) => {
+                    state = next_state;
+                    action = next_action;
+                }
+            }
+        }
+        td_state.episodes += 1;
+    }
+
+    #[bench]
+    fn b_td_lambda_control_dense(b: &mut Bencher) {
+        let env = Easy21Env::default();
+        let mut rng = StdRng::seed_from_u64(0);
+        let h = Hyperparameters {
+            lambda: 0.9,
+            ..Hyperparameters::default()
+        };
+        let mut td_state = TDControlState::init(h);
+        let mut traces = vec![0.0; td_state.q.0.len()];
+        b.iter(|| {
+            for _ in 0..EPISODES {
+                td_lambda_control_dense(&env, &mut rng, &h.exploration, &mut td_state, &mut traces);
+            }
+            black_box(td_state.episodes)
+        });
+    }
+
+    #[bench]
+    fn b_td_lambda_prediction(b: &mut Bencher) {
+        let env = Easy21Env::default();
+        let mut rng = StdRng::seed_from_u64(0);
+        let h = Hyperparameters {
+            lambda: 0.9,
+            ..Hyperparameters::default()
+        };
+        let mut td_state = TDState::init(h, example_policy);
+        b.iter(|| {
+            for _ in 0..EPISODES {
+                td_lambda_prediction(&env, &mut rng, td_state.policy, &mut td_state);
+            }
+            black_box(td_state.episodes)
+        });
+    }
+}
@@ -0,0 +1,217 @@
+//! Baird's counterexample, where off-policy semi-gradient TD diverges with linear
+//! features that can represent the true values exactly.
+//!
+//! Seven states, six upper ones and a lower one, and two actions: the dashed action
+//! jumps to one of the upper states at random and the solid action to the lower state.
+//! All rewards are zero, so every state is worth zero. The behaviour policy takes the
+//! dashed action with probability 6/7 and the target policy always takes the solid one.
+//! Instead of discounting, every step ends the episode with probability
+//! `1 - DISCOUNT`, which leaves the expected updates of the discounted task unchanged.
+
+use plotters::prelude::*;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use super::{
+    exploration::{Counted, Exploration},
+    features::StateFeatureExtractor,
+    ApproxPredictionState, Hyperparameters, LinearPredictionMethod, Vector,
+};
+use crate::easy_21::step_size::StepSize;
+use crate::env::{Environment, Sample};
+
+/// Probability that an episode continues after a step.
+const DISCOUNT: f64 = 0.99;
+
+/// The lower state. The upper states are 0 to 5.
+const LOWER: usize = 6;
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Baird;
+
+impl Environment for Baird {
+    type State = usize;
+    /// Whether the action is the solid one.
+    type Action = bool;
+
+    const ACTIONS: &'static [bool] = &[false, true];
+
+    fn reset<R: Rng>(&self, rng: &mut R) -> usize {
+        rng.gen_range(0..=LOWER)
+    }
+
+    fn step<R: Rng>(&self, rng: &mut R, _state: usize, solid: bool) -> Sample<usize> {
+        let state = if solid {
+            LOWER
+        } else {
+            rng.gen_range(0..LOWER)
+        };
+        Sample {
+            state,
+            reward: 0.0,
+            terminal: !rng.gen_bool(DISCOUNT),
+        }
+    }
+}
+
+/// The features of the counterexample: the value of an upper state `i` is `2 w_i + w_7`
+/// and that of the lower state `w_6 + 2 w_7`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BairdFeatures;
+
+impl StateFeatureExtractor<Baird> for BairdFeatures {
+    fn state_dimension(&self) -> usize {
+        8
+    }
+
+    fn for_each_state<F: FnMut(usize, f64)>(&self, state: &usize, mut f: F) {
+        if *state == LOWER {
+            f(LOWER, 1.0);
+            f(7, 2.0);
+        } else {
+            f(*state, 2.0);
+            f(7, 1.0);
+        }
+    }
+}
+
+/// The dashed action with probability 6/7, whatever the target policy.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Behaviour;
+
+impl Exploration<Baird> for Behaviour {
+    fn probabilities<Q: Counted<Baird>>(&self, _q: &Q, _state: &usize) -> Vec<f64> {
+        vec![6.0 / 7.0, 1.0 / 7.0]
+    }
+}
+
+fn solid(_rng: &mut StdRng, _state: &usize) -> bool {
+    true
+}
+
+/// Runs `method` for up to `episodes` episodes from the usual initial weights, and
+/// returns the largest absolute weight after each episode. Stops early if the weights
+/// overflow.
+pub fn run(method: LinearPredictionMethod, episodes: usize, rng: &mut StdRng) -> Vec<f64> {
+    let h = Hyperparameters {
+        lambda: 0.0,
+        step_size: StepSize::Constant(0.01),
+        ..Hyperparameters::default()
+    };
+    let mut approx_state = ApproxPredictionState {
+        v: Vector {
+            w: vec![1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 10.0, 1.0],
+        },
+        method,
+        ..ApproxPredictionState::init(h, BairdFeatures, solid)
+    };
+    let mut curve = vec![];
+    for _ in 0..episodes {
+        super::off_policy_td_prediction(&Baird, rng, &Behaviour, solid, &mut approx_state);
+        let weights = &approx_state.v.w;
+        if !weights.iter().all(|w| w.is_finite()) {
+            break;
+        }
+        curve.push(weights.iter().fold(0.0, |a: f64, w| a.max(w.abs())));
+    }
+    curve
+}
+
+const METHODS: [(LinearPredictionMethod, &str); 3] = [
+    (
+        LinearPredictionMethod::OffPolicyTDLambda,
+        "Semi-gradient TD",
+    ),
+    (LinearPredictionMethod::GTD2, "GTD2"),
+    (LinearPredictionMethod::TDC, "TDC"),
+];
+
+/// Plots the weights of semi-gradient TD against those of the gradient-TD methods.
+pub struct Demo {
+    pub episodes: usize,
+    /// Largest weight after each episode, per method in [`METHODS`].
+    curves: Vec<Vec<f64>>,
+}
+
+impl Default for Demo {
+    fn default() -> Self {
+        Self {
+            episodes: 1000,
+            curves: vec![],
+        }
+    }
+}
+
+impl Demo {
+    pub fn ui(&mut self, ui: &mut egui::Ui, seed: u64) {
+        ui.horizontal(|ui| {
+            ui.label("Episodes:");
+            ui.add(egui::DragValue::new(&mut self.episodes).clamp_range(1..=100_000));
+            if ui.button("Run").clicked() {
+                self.curves = METHODS
+                    .iter()
+                    .map(|(method, _)| {
+                        run(*method, self.episodes, &mut StdRng::seed_from_u64(seed))
+                    })
+                    .collect();
+            }
+        });
+        ui.label(
+            "Largest weight after each episode. Semi-gradient TD stops when its weights \
+             overflow.",
+        );
+
+        let size = egui::vec2(ui.available_width().max(400.0), 300.0);
+        let (rect, _) = ui.allocate_exact_size(size, egui::Sense::hover());
+        let chart_ui = ui.child_ui(rect, egui::Layout::default());
+        let area = egui_plotter::EguiBackend::new(&chart_ui).into_drawing_area();
+        let max = self
+            .curves
+            .iter()
+            .flatten()
+            .fold(10.0, |a: f64, w| a.max(*w));
+        let mut chart = ChartBuilder::on(&area)
+            .margin(10)
+            .x_label_area_size(20)
+            .y_label_area_size(40)
+            .build_cartesian_2d(0.0..self.episodes as f64, (0.1..max).log_scale())
+            .unwrap();
+
+        chart.configure_mesh().draw().unwrap();
+
+        for (i, (curve, (_, name))) in self.curves.iter().zip(METHODS).enumerate() {
+            let color = Palette99::pick(i).to_rgba();
+            let points = curve.iter().enumerate().map(|(e, w)| (e as f64 + 1.0, *w));
+            chart
+                .draw_series(LineSeries::new(points, &color))
+                .unwrap()
+                .label(name)
+                .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 15, y)], color));
+        }
+
+        chart
+            .configure_series_labels()
+            .background_style(WHITE.mix(0.8))
+            .border_style(BLACK)
+            .draw()
+            .unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_divergence() {
+        let runs: Vec<Vec<f64>> = METHODS
+            .iter()
+            .map(|(method, _)| run(*method, 1000, &mut StdRng::seed_from_u64(0)))
+            .collect();
+        let largest = |curve: &Vec<f64>| curve.iter().fold(0.0, |a: f64, w| a.max(*w));
+        assert!(largest(&runs[0]) > 1e6, "{}", largest(&runs[0]));
+        for curve in &runs[1..] {
+            assert_eq!(curve.len(), 1000);
+            assert!(largest(curve) < 100.0, "{}", largest(curve));
+        }
+    }
+}
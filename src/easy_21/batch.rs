@@ -0,0 +1,513 @@
+//! Least-squares batch methods for linear values.
+//!
+//! Instead of following the TD updates with a small step size, these methods collect
+//! transitions first and then solve directly for the weights at which the expected TD
+//! update is zero. That makes them independent of any step size, at the cost of a linear
+//! system with one row per feature. A small multiple of the identity is added to every
+//! system, so that features the samples never activate get weight zero instead of making
+//! the system singular.
+//!
+//! The systems are dense and solved by elimination in time cubic in the number of
+//! features, so these methods only suit a few dozen features, and refuse more than
+//! [`MAX_DIMENSION`]. [`BatchState`] runs them in the app with the [`Cuboid`] features:
+//! LSTD(λ) adds every episode of [`example_policy`] to one growing system and solves it
+//! again after each. LSPI has to rebuild its systems whenever the greedy policy changes,
+//! so it keeps only the last [`LSPI_TRANSITIONS`] transitions of its exploring episodes,
+//! and reruns over them every [`LSPI_INTERVAL`] episodes, and at powers of two before
+//! that. That bounds both the memory and the time of a rerun.
+
+use std::collections::VecDeque;
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    dp, example_policy,
+    exploration::{Counted, Exploration},
+    features::{Cuboid, FeatureExtractor, StateFeatureExtractor},
+    greedy, max_q, persistence, Action, Easy21Env, Easy21State, HasQ, HasV, Hyperparameters,
+    SparseVector, State, Trace, Traces, Vector,
+};
+use crate::env::Environment;
+
+/// Added to the diagonal of every system.
+const REGULARIZATION: f64 = 1e-6;
+
+/// Pivots below this count as zero.
+const SINGULAR: f64 = 1e-12;
+
+/// Largest number of weights to solve for.
+pub const MAX_DIMENSION: usize = 256;
+
+/// LSPI in the app stops once no weight moves by more than this, or after
+/// [`LSPI_ITERATIONS`].
+const LSPI_TOLERANCE: f64 = 1e-6;
+
+const LSPI_ITERATIONS: usize = 20;
+
+/// Transitions that LSPI in the app keeps, the most recent ones.
+pub const LSPI_TRANSITIONS: usize = 10_000;
+
+/// Episodes between the reruns of LSPI in the app.
+pub const LSPI_INTERVAL: i32 = 500;
+
+/// A step of an episode.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(bound(
+    serialize = "E::State: Serialize, E::Action: Serialize",
+    deserialize = "E::State: Deserialize<'de>, E::Action: Deserialize<'de>"
+))]
+pub struct Transition<E: Environment> {
+    pub state: E::State,
+    pub action: E::Action,
+    pub reward: f64,
+    /// The successor state, or `None` if the step ended the episode.
+    pub next_state: Option<E::State>,
+}
+
+/// Plays `episodes` episodes with `policy` and returns their transitions, one list per
+/// episode.
+pub fn collect<E: Environment, R: Rng, P: Fn(&mut R, &E::State) -> E::Action>(
+    env: &E,
+    rng: &mut R,
+    policy: P,
+    episodes: usize,
+) -> Vec<Vec<Transition<E>>> {
+    (0..episodes)
+        .map(|_| {
+            let mut state = env.reset(rng);
+            let mut transitions = vec![];
+            loop {
+                let action = policy(rng, &state);
+                let sample = env.step(rng, state, action);
+                transitions.push(Transition {
+                    state,
+                    action,
+                    reward: sample.reward,
+                    next_state: (!sample.terminal).then_some(sample.state),
+                });
+                if sample.terminal {
+                    return transitions;
+                }
+                state = sample.state;
+            }
+        })
+        .collect()
+}
+
+/// Solves `a x = b` by Gaussian elimination with partial pivoting. `a` is given by its
+/// rows. Returns `None` if `a` is singular.
+pub fn solve(mut a: Vec<Vec<f64>>, mut b: Vec<f64>) -> Option<Vec<f64>> {
+    let n = b.len();
+    for column in 0..n {
+        let pivot = (column..n)
+            .max_by(|&i, &j| a[i][column].abs().total_cmp(&a[j][column].abs()))
+            .unwrap();
+        if a[pivot][column].abs() < SINGULAR {
+            return None;
+        }
+        a.swap(column, pivot);
+        b.swap(column, pivot);
+        let (upper, lower) = a.split_at_mut(column + 1);
+        let pivot_row = &upper[column];
+        for (i, row) in lower.iter_mut().enumerate() {
+            let factor = row[column] / pivot_row[column];
+            for (x, p) in row[column..].iter_mut().zip(&pivot_row[column..]) {
+                *x -= factor * p;
+            }
+            b[column + 1 + i] -= factor * b[column];
+        }
+    }
+    let mut x = vec![0.0; n];
+    for row in (0..n).rev() {
+        let sum: f64 = (row + 1..n).map(|k| a[row][k] * x[k]).sum();
+        x[row] = (b[row] - sum) / a[row][row];
+    }
+    Some(x)
+}
+
+/// The linear system `A w = b` of the fixed point of linear TD, built up one transition
+/// at a time. `A` is given by its rows.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct System {
+    pub a: Vec<Vec<f64>>,
+    pub b: Vec<f64>,
+}
+
+impl System {
+    /// The regularized system of no transitions.
+    pub fn init(dimension: usize) -> Self {
+        assert!(
+            dimension <= MAX_DIMENSION,
+            "{} weights are too many for a dense system",
+            dimension
+        );
+        let mut a = vec![vec![0.0; dimension]; dimension];
+        for (i, row) in a.iter_mut().enumerate() {
+            row[i] = REGULARIZATION;
+        }
+        Self {
+            a,
+            b: vec![0.0; dimension],
+        }
+    }
+
+    /// Adds `z (x - next_x)ᵀ` to `A` and `z reward` to `b`.
+    fn add<'a>(
+        &mut self,
+        z: impl Iterator<Item = &'a (usize, f64)>,
+        x: &SparseVector,
+        next_x: &SparseVector,
+        reward: f64,
+    ) {
+        for &(i, z) in z {
+            for &(j, x) in &x.entries {
+                self.a[i][j] += z * x;
+            }
+            for &(j, x) in &next_x.entries {
+                self.a[i][j] -= z * x;
+            }
+            self.b[i] += z * reward;
+        }
+    }
+
+    fn solve(&self) -> Vector {
+        let w =
+            solve(self.a.clone(), self.b.clone()).expect("the regularized system is not singular");
+        Vector { w }
+    }
+
+    /// Adds the transitions of an episode with the traces of TD(λ).
+    fn add_episode<E: Environment, F: StateFeatureExtractor<E>>(
+        &mut self,
+        features: &F,
+        episode: &[Transition<E>],
+        lambda: f64,
+    ) {
+        let mut traces = Traces::default();
+        for transition in episode {
+            let x = features.extract_state(&transition.state);
+            let next_x = match &transition.next_state {
+                Some(state) => features.extract_state(state),
+                None => SparseVector::default(),
+            };
+            Trace::Accumulating.update_linear(&mut traces, &x, lambda, 0.0);
+            self.add(traces.iter(), &x, &next_x, transition.reward);
+        }
+    }
+}
+
+/// LSTD(λ): the weights of linear state values at which the expected update of TD(λ)
+/// over `episodes` is zero.
+pub fn lstd_lambda<E: Environment, F: StateFeatureExtractor<E>>(
+    features: &F,
+    episodes: &[Vec<Transition<E>>],
+    lambda: f64,
+) -> Vector {
+    let mut system = System::init(features.state_dimension());
+    for episode in episodes {
+        system.add_episode(features, episode, lambda);
+    }
+    system.solve()
+}
+
+/// Linear action values, as the weights together with their features.
+pub struct LinearQ<'a, F> {
+    pub features: &'a F,
+    pub weights: &'a Vector,
+}
+
+impl<E: Environment, F: FeatureExtractor<E>> HasQ<E> for LinearQ<'_, F> {
+    fn get_q(&self, state: &E::State, action: &E::Action) -> f64 {
+        self.features.value(state, action, self.weights)
+    }
+}
+
+/// Linear values keep no counts, so every state looks unvisited.
+impl<E: Environment, F: FeatureExtractor<E>> Counted<E> for LinearQ<'_, F> {
+    fn count(&self, _state: &E::State, _action: &E::Action) -> i32 {
+        0
+    }
+}
+
+/// LSTDQ: the weights of the linear action values of the policy that is greedy with
+/// respect to `weights`, from transitions of any behaviour policy.
+pub fn lstdq<E: Environment, F: FeatureExtractor<E>>(
+    features: &F,
+    transitions: &[Transition<E>],
+    weights: &Vector,
+) -> Vector {
+    let q = LinearQ { features, weights };
+    let mut system = System::init(features.dimension());
+    for transition in transitions {
+        let x = features.extract(&transition.state, &transition.action);
+        let next_x = match &transition.next_state {
+            Some(state) => features.extract(state, &greedy(&q, state)),
+            None => SparseVector::default(),
+        };
+        system.add(x.entries.iter(), &x, &next_x, transition.reward);
+    }
+    system.solve()
+}
+
+/// LSPI: alternates LSTDQ and greedy improvement over the same `transitions`, starting
+/// from `weights`, until no weight moves by more than `tolerance` or after
+/// `max_iterations`. Returns the weights and the number of iterations.
+pub fn lspi<E: Environment, F: FeatureExtractor<E>>(
+    features: &F,
+    transitions: &[Transition<E>],
+    mut weights: Vector,
+    tolerance: f64,
+    max_iterations: usize,
+) -> (Vector, usize) {
+    for iteration in 1..=max_iterations {
+        let next = lstdq(features, transitions, &weights);
+        let change = next
+            .w
+            .iter()
+            .zip(&weights.w)
+            .fold(0.0, |a: f64, (x, y)| a.max((x - y).abs()));
+        weights = next;
+        if change <= tolerance {
+            return (weights, iteration);
+        }
+    }
+    (weights, max_iterations)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BatchMethod {
+    /// LSTD(λ) of [`example_policy`].
+    LSTDLambdaPrediction,
+    /// LSPI from episodes that explore around its greedy policy.
+    LSPIControl,
+}
+
+/// A least-squares method with the [`Cuboid`] features, collecting one episode per
+/// update.
+pub struct BatchState {
+    /// Weights of the state values for LSTD(λ), and of the action values for LSPI.
+    pub weights: Vector,
+    /// The system of all episodes so far, for LSTD(λ).
+    pub system: System,
+    /// The last [`LSPI_TRANSITIONS`] transitions, for LSPI.
+    pub transitions: VecDeque<Transition<Easy21Env>>,
+    pub episodes: i32,
+    pub method: BatchMethod,
+    pub hyperparameters: Hyperparameters,
+}
+
+impl BatchState {
+    pub fn init(hyperparameters: Hyperparameters, method: BatchMethod) -> Self {
+        let dimension = match method {
+            BatchMethod::LSTDLambdaPrediction => Cuboid.state_dimension(),
+            BatchMethod::LSPIControl => Cuboid.dimension(),
+        };
+        Self {
+            weights: Vector::init(dimension),
+            system: System::init(dimension),
+            transitions: VecDeque::new(),
+            episodes: 0,
+            method,
+            hyperparameters,
+        }
+    }
+
+    fn q(&self) -> LinearQ<'_, Cuboid> {
+        LinearQ {
+            features: &Cuboid,
+            weights: &self.weights,
+        }
+    }
+}
+
+impl HasV for BatchState {
+    fn get_v(&self, state: &State) -> f64 {
+        match self.method {
+            BatchMethod::LSTDLambdaPrediction => Cuboid.state_value(state, &self.weights),
+            BatchMethod::LSPIControl => max_q(&self.q(), state),
+        }
+    }
+}
+
+impl Easy21State for BatchState {
+    fn update(&mut self, env: &Easy21Env, rng: &mut StdRng) {
+        let h = self.hyperparameters;
+        match self.method {
+            BatchMethod::LSTDLambdaPrediction => {
+                let episode = collect(env, rng, example_policy, 1).remove(0);
+                self.system.add_episode(&Cuboid, &episode, h.lambda);
+                self.weights = self.system.solve();
+                self.episodes += 1;
+            }
+            BatchMethod::LSPIControl => {
+                let q = self.q();
+                let policy = |rng: &mut StdRng, state: &State| h.exploration.choose(rng, &q, state);
+                let episode = collect(env, rng, policy, 1).remove(0);
+                self.transitions.extend(episode);
+                while self.transitions.len() > LSPI_TRANSITIONS {
+                    self.transitions.pop_front();
+                }
+                self.episodes += 1;
+                let early =
+                    self.episodes < LSPI_INTERVAL && (self.episodes as u32).is_power_of_two();
+                if early || self.episodes % LSPI_INTERVAL == 0 {
+                    // Starting from the last weights, as the batch changed only in part.
+                    let transitions = self.transitions.make_contiguous();
+                    let weights = self.weights.clone();
+                    self.weights = lspi(
+                        &Cuboid,
+                        transitions,
+                        weights,
+                        LSPI_TOLERANCE,
+                        LSPI_ITERATIONS,
+                    )
+                    .0;
+                }
+            }
+        }
+    }
+    fn episodes(&self) -> i32 {
+        self.episodes
+    }
+    fn policy(&self, state: &State) -> Action {
+        match self.method {
+            // Fixed seed, as for the other prediction methods.
+            BatchMethod::LSTDLambdaPrediction => {
+                example_policy(&mut StdRng::seed_from_u64(0), state)
+            }
+            BatchMethod::LSPIControl => greedy(&self.q(), state),
+        }
+    }
+    fn rms_error(&self, reference: &dp::Reference) -> f64 {
+        match self.method {
+            BatchMethod::LSTDLambdaPrediction => reference.example_policy.rms_error_v(self),
+            BatchMethod::LSPIControl => reference.optimal.rms_error(&self.q()),
+        }
+    }
+    fn set_hyperparameters(&mut self, h: Hyperparameters) {
+        self.hyperparameters = h;
+    }
+    fn save(&self) -> persistence::Learner {
+        let weights = self.weights.clone();
+        let episodes = self.episodes;
+        let hyperparameters = self.hyperparameters;
+        match self.method {
+            BatchMethod::LSTDLambdaPrediction => persistence::Learner::LSTDLambdaPrediction {
+                weights,
+                system: self.system.clone(),
+                episodes,
+                hyperparameters,
+            },
+            BatchMethod::LSPIControl => persistence::Learner::LSPIControl {
+                weights,
+                transitions: self.transitions.clone(),
+                episodes,
+                hyperparameters,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+    use crate::easy_21::{
+        dp, example_policy,
+        features::{Cuboid, Features},
+        Action, Algorithm, ApproxPredictionState, Easy21Env, Hyperparameters,
+    };
+
+    #[test]
+    fn test_solve() {
+        // Needs a row swap, as the first pivot is zero.
+        let a = vec![
+            vec![0.0, 2.0, 1.0],
+            vec![1.0, 1.0, 0.0],
+            vec![2.0, 0.0, 3.0],
+        ];
+        let x = solve(a, vec![7.0, 3.0, 11.0]).unwrap();
+        for (x, expected) in x.iter().zip([1.0, 2.0, 3.0]) {
+            assert!((x - expected).abs() < 1e-12, "{}", x);
+        }
+        assert!(solve(vec![vec![1.0, 2.0], vec![2.0, 4.0]], vec![1.0, 2.0]).is_none());
+    }
+
+    #[test]
+    fn test_least_squares() {
+        let env = Easy21Env::default();
+        let reference = dp::Reference::new(&env.rules);
+        let mut rng = StdRng::seed_from_u64(0);
+
+        let episodes = collect(&env, &mut rng, example_policy, 10_000);
+        let weights = lstd_lambda(&Features::Cuboid, &episodes, 0.5);
+        let approx_state = ApproxPredictionState {
+            v: weights,
+            ..ApproxPredictionState::init(
+                Hyperparameters::default(),
+                Features::Cuboid,
+                example_policy,
+            )
+        };
+        // About as close as the cuboids get with any step size.
+        let error = reference.example_policy.rms_error_v(&approx_state);
+        assert!(error < 0.35, "{}", error);
+
+        let random = |rng: &mut StdRng, _: &_| {
+            if rng.gen() {
+                Action::Hit
+            } else {
+                Action::Stick
+            }
+        };
+        let transitions: Vec<_> = collect(&env, &mut rng, random, 10_000)
+            .into_iter()
+            .flatten()
+            .collect();
+        let (weights, iterations) = lspi(
+            &Cuboid,
+            &transitions,
+            Vector::init(Cuboid.dimension()),
+            1e-6,
+            20,
+        );
+        let q = LinearQ {
+            features: &Cuboid,
+            weights: &weights,
+        };
+        assert!(iterations < 20);
+        let error = reference.optimal.rms_error(&q);
+        assert!(error < 0.25, "{}", error);
+    }
+
+    #[test]
+    fn test_batch_state() {
+        let env = Easy21Env::default();
+        let reference = dp::Reference::new(&env.rules);
+        let mut rng = StdRng::seed_from_u64(0);
+        for (algorithm, bound) in [
+            (Algorithm::LSTDLambdaPrediction, 0.35),
+            (Algorithm::LSPIControl, 0.25),
+        ] {
+            let mut state = algorithm.initial_state(algorithm.hyperparameters());
+            for _ in 0..8192 {
+                state.update(&env, &mut rng);
+            }
+            let error = state.rms_error(&reference);
+            assert!(error < bound, "{:?}: {}", algorithm, error);
+            if let persistence::Learner::LSPIControl { transitions, .. } = state.save() {
+                assert!(transitions.len() <= LSPI_TRANSITIONS);
+            }
+            // Snapshots keep the batch, so a loaded learner goes on like the original.
+            let format = persistence::Format::Binary;
+            let snapshot = persistence::Snapshot::new(env.rules, 0, state.save());
+            let snapshot = format.decode(&format.encode(&snapshot)).unwrap();
+            let mut loaded = snapshot.learner.into_state();
+            state.update(&env, &mut StdRng::seed_from_u64(1));
+            loaded.update(&env, &mut StdRng::seed_from_u64(1));
+            assert_eq!(loaded.rms_error(&reference), state.rms_error(&reference));
+        }
+    }
+}
@@ -0,0 +1,520 @@
+//! Exact solution of Easy21 by dynamic programming.
+//!
+//! The transition probabilities are enumerated from [`Card::outcomes`], i.e. the same
+//! distribution that [`Card::draw`] samples from, and the dealer's rollout in [`step`]
+//! is reproduced as a distribution over final dealer sums. Everything is computed for a
+//! given set of [`Easy21Rules`], so the reference values follow any change to the rules.
+//!
+//! [`step`]: super::step
+
+use rand::{rngs::StdRng, SeedableRng};
+
+use super::{
+    example_policy, signum, Action, Card, Easy21Env, Easy21Rules, HasQ, HasV, State, Q, V,
+};
+use crate::env::Environment;
+
+/// Iterations stop once no value changes by more than this.
+const TOLERANCE: f64 = 1e-12;
+
+/// Exact values to measure the learners against.
+#[derive(Clone)]
+pub struct Reference {
+    /// Q* and V*, for the control algorithms.
+    pub optimal: Solution,
+    /// Q^π and V^π of [`example_policy`], which the prediction algorithms evaluate.
+    pub example_policy: Solution,
+}
+
+impl Reference {
+    pub fn new(rules: &Easy21Rules) -> Self {
+        // `example_policy` ignores its random number generator.
+        let mut rng = StdRng::seed_from_u64(0);
+        Self {
+            optimal: value_iteration(rules),
+            example_policy: evaluate(rules, |s| example_policy(&mut rng, s)),
+        }
+    }
+}
+
+/// Action values and state values of a policy under a set of rules.
+#[derive(Clone)]
+pub struct Solution {
+    pub rules: Easy21Rules,
+    pub q: Q<f64>,
+    pub v: V<f64>,
+}
+
+impl Solution {
+    fn from_q(rules: &Easy21Rules, q: Q<f64>) -> Self {
+        let mut v = V::init(0.0);
+        for state in states(rules) {
+            v.set(
+                &state,
+                q.get(&state, &Action::Hit)
+                    .max(q.get(&state, &Action::Stick)),
+            );
+        }
+        Self {
+            rules: *rules,
+            q,
+            v,
+        }
+    }
+
+    pub fn policy(&self, state: &State) -> Action {
+        greedy(&self.q, state)
+    }
+
+    /// Mean squared difference between `q` and `self.q` over all non-terminal
+    /// state-action pairs.
+    pub fn mean_squared_error<H: HasQ>(&self, q: &H) -> f64 {
+        let mut sum = 0.0;
+        let mut n = 0;
+        for state in states(&self.rules) {
+            for action in Easy21Env::ACTIONS {
+                let diff = q.get_q(&state, action) - self.q.get(&state, action);
+                sum += diff * diff;
+                n += 1;
+            }
+        }
+        sum / n as f64
+    }
+
+    /// Root mean squared difference between `q` and `self.q` over all non-terminal
+    /// state-action pairs.
+    pub fn rms_error<H: HasQ>(&self, q: &H) -> f64 {
+        self.mean_squared_error(q).sqrt()
+    }
+
+    /// Root mean squared difference between `v` and `self.v` over all non-terminal
+    /// states.
+    pub fn rms_error_v<H: HasV>(&self, v: &H) -> f64 {
+        let mut sum = 0.0;
+        let mut n = 0;
+        for state in states(&self.rules) {
+            let diff = v.get_v(&state) - self.v.get(&state);
+            sum += diff * diff;
+            n += 1;
+        }
+        (sum / n as f64).sqrt()
+    }
+}
+
+/// All non-terminal states: every valid player sum against every first dealer card.
+pub fn states(rules: &Easy21Rules) -> impl Iterator<Item = State> {
+    // The player's first card may be outside the valid sums, as in `Dealer::new`.
+    let mut players: Vec<i32> = rules.valid_sums().chain(rules.card_values()).collect();
+    players.sort_unstable();
+    players.dedup();
+    let dealers = rules.card_values();
+    players
+        .into_iter()
+        .flat_map(move |player| dealers.clone().map(move |dealer| State { dealer, player }))
+}
+
+fn greedy(q: &Q<f64>, state: &State) -> Action {
+    if q.get(state, &Action::Hit) > q.get(state, &Action::Stick) {
+        Action::Hit
+    } else {
+        Action::Stick
+    }
+}
+
+/// Where the dealer's turn ends: the probability of sticking on each sum, and of
+/// going bust.
+#[derive(Clone, Default)]
+struct DealerOutcome {
+    sticks: Vec<(i32, f64)>,
+    bust: f64,
+}
+
+impl DealerOutcome {
+    fn add(&mut self, other: &DealerOutcome, p: f64) {
+        for &(sum, q) in &other.sticks {
+            self.add_stick(sum, p * q);
+        }
+        self.bust += p * other.bust;
+    }
+
+    fn add_stick(&mut self, sum: i32, p: f64) {
+        match self.sticks.iter_mut().find(|(s, _)| *s == sum) {
+            Some((_, q)) => *q += p,
+            None => self.sticks.push((sum, p)),
+        }
+    }
+
+    fn distance(&self, other: &DealerOutcome) -> f64 {
+        self.sticks
+            .iter()
+            .map(|(sum, p)| {
+                let q = other
+                    .sticks
+                    .iter()
+                    .find(|(s, _)| s == sum)
+                    .map_or(0.0, |x| x.1);
+                (p - q).abs()
+            })
+            .fold((self.bust - other.bust).abs(), f64::max)
+    }
+
+    /// Expected reward for the player when sticking on `player`.
+    fn reward(&self, player: i32) -> f64 {
+        self.bust
+            + self
+                .sticks
+                .iter()
+                .map(|(dealer, p)| p * signum(player - dealer) as f64)
+                .sum::<f64>()
+    }
+}
+
+/// Outcome of the dealer's rollout for every sum the dealer might still be hitting on.
+struct Dealer {
+    outcomes: Vec<(i32, DealerOutcome)>,
+}
+
+impl Dealer {
+    fn new(rules: &Easy21Rules) -> Self {
+        // The first card may be below `min_sum`; the dealer hits on it all the same.
+        let mut hitting: Vec<i32> = rules
+            .valid_sums()
+            .chain(rules.card_values())
+            .filter(|d| *d < rules.dealer_sticks_at)
+            .collect();
+        hitting.sort_unstable();
+        hitting.dedup();
+        let mut outcomes: Vec<(i32, DealerOutcome)> = hitting
+            .iter()
+            .map(|d| (*d, DealerOutcome::default()))
+            .collect();
+
+        // The dealer's sum can go down again, so iterate to a fixed point.
+        loop {
+            let next: Vec<(i32, DealerOutcome)> = hitting
+                .iter()
+                .map(|&dealer| {
+                    let mut outcome = DealerOutcome::default();
+                    for (card, p) in Card::outcomes(rules) {
+                        let sum = card.add_to(dealer);
+                        if rules.is_bust(sum) {
+                            outcome.bust += p;
+                        } else if sum >= rules.dealer_sticks_at {
+                            outcome.add_stick(sum, p);
+                        } else {
+                            let (_, o) = outcomes.iter().find(|(d, _)| *d == sum).unwrap();
+                            outcome.add(o, p);
+                        }
+                    }
+                    (dealer, outcome)
+                })
+                .collect();
+            let delta = outcomes
+                .iter()
+                .zip(&next)
+                .map(|((_, old), (_, new))| new.distance(old))
+                .fold(0.0, f64::max);
+            outcomes = next;
+            if delta < TOLERANCE {
+                break Self { outcomes };
+            }
+        }
+    }
+
+    /// Expected reward of sticking in `state`.
+    fn stick(&self, state: &State) -> f64 {
+        match self.outcomes.iter().find(|(d, _)| *d == state.dealer) {
+            Some((_, outcome)) => outcome.reward(state.player),
+            None => {
+                // The first card is at least `dealer_sticks_at`, so the dealer sticks on it.
+                let mut outcome = DealerOutcome::default();
+                outcome.add_stick(state.dealer, 1.0);
+                outcome.reward(state.player)
+            }
+        }
+    }
+}
+
+/// One backup of the action values, where `v` gives the value of continuing in a state.
+fn backup<F: Fn(&State) -> f64>(rules: &Easy21Rules, dealer: &Dealer, v: F) -> Q<f64> {
+    let mut q: Q<f64> = Q::init(0.0);
+    for state in states(rules) {
+        let hit: f64 = Card::outcomes(rules)
+            .map(|(card, p)| {
+                let player = card.add_to(state.player);
+                if rules.is_bust(player) {
+                    -p
+                } else {
+                    p * v(&State { player, ..state })
+                }
+            })
+            .sum();
+        q.set(&state, &Action::Hit, hit);
+        q.set(&state, &Action::Stick, dealer.stick(&state));
+    }
+    q
+}
+
+fn max_diff(a: &Q<f64>, b: &Q<f64>) -> f64 {
+    a.0.iter()
+        .zip(&b.0)
+        .map(|(x, y)| (x - y).abs())
+        .fold(0.0, f64::max)
+}
+
+/// Computes Q* by iterating the Bellman optimality backup to convergence.
+pub fn value_iteration(rules: &Easy21Rules) -> Solution {
+    let dealer = Dealer::new(rules);
+    let mut q: Q<f64> = Q::init(0.0);
+    loop {
+        let next = backup(rules, &dealer, |s| {
+            q.get(s, &Action::Hit).max(q.get(s, &Action::Stick))
+        });
+        let delta = max_diff(&q, &next);
+        q = next;
+        if delta < TOLERANCE {
+            break Solution::from_q(rules, q);
+        }
+    }
+}
+
+/// Computes Q^π of a deterministic policy by iterating the Bellman expectation backup.
+pub fn policy_evaluation<P: Fn(&State) -> Action>(rules: &Easy21Rules, policy: P) -> Q<f64> {
+    let dealer = Dealer::new(rules);
+    let mut q: Q<f64> = Q::init(0.0);
+    loop {
+        let next = backup(rules, &dealer, |s| q.get(s, &policy(s)));
+        let delta = max_diff(&q, &next);
+        q = next;
+        if delta < TOLERANCE {
+            break q;
+        }
+    }
+}
+
+/// Computes Q^π and V^π of a deterministic policy.
+pub fn evaluate<P: FnMut(&State) -> Action>(rules: &Easy21Rules, mut policy: P) -> Solution {
+    let mut actions: V<Action> = V::init(Action::Stick);
+    for state in states(rules) {
+        actions.set(&state, policy(&state));
+    }
+    let q = policy_evaluation(rules, |s| actions.get(s));
+    let mut v = V::init(0.0);
+    for state in states(rules) {
+        v.set(&state, q.get(&state, &actions.get(&state)));
+    }
+    Solution {
+        rules: *rules,
+        q,
+        v,
+    }
+}
+
+/// Computes Q^π and V^π of a stochastic policy, given the probability of each action in
+/// the order of [`Easy21Env::ACTIONS`].
+pub fn evaluate_stochastic<P: FnMut(&State) -> Vec<f64>>(
+    rules: &Easy21Rules,
+    mut policy: P,
+) -> Solution {
+    let mut probabilities: V<[f64; 2]> = V::init([0.0; 2]);
+    for state in states(rules) {
+        let p = policy(&state);
+        probabilities.set(&state, [p[0], p[1]]);
+    }
+    let expected = |q: &Q<f64>, s: &State| {
+        Easy21Env::ACTIONS
+            .iter()
+            .zip(probabilities.get(s))
+            .map(|(a, p)| p * q.get(s, a))
+            .sum::<f64>()
+    };
+    let dealer = Dealer::new(rules);
+    let mut q: Q<f64> = Q::init(0.0);
+    loop {
+        let next = backup(rules, &dealer, |s| expected(&q, s));
+        let delta = max_diff(&q, &next);
+        q = next;
+        if delta < TOLERANCE {
+            break;
+        }
+    }
+    let mut v = V::init(0.0);
+    for state in states(rules) {
+        v.set(&state, expected(&q, &state));
+    }
+    Solution {
+        rules: *rules,
+        q,
+        v,
+    }
+}
+
+/// Computes Q* by alternating policy evaluation and greedy policy improvement, starting
+/// from the policy that always sticks.
+pub fn policy_iteration(rules: &Easy21Rules) -> Solution {
+    let mut policy: V<Action> = V::init(Action::Stick);
+    loop {
+        let q = policy_evaluation(rules, |s| policy.get(s));
+        let mut stable = true;
+        for state in states(rules) {
+            let improved = greedy(&q, &state);
+            if improved != policy.get(&state) {
+                policy.set(&state, improved);
+                stable = false;
+            }
+        }
+        if stable {
+            break Solution::from_q(rules, q);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::Rng;
+
+    use super::*;
+    use crate::easy_21::step;
+
+    #[test]
+    fn test_value_and_policy_iteration_agree() {
+        let rules = Easy21Rules {
+            dealer_sticks_at: 16,
+            red_probability: 0.4,
+            ..Default::default()
+        };
+        let vi = value_iteration(&rules);
+        let pi = policy_iteration(&rules);
+        assert!(max_diff(&vi.q, &pi.q) < 1e-9);
+        for state in states(&rules) {
+            assert_eq!(vi.policy(&state), pi.policy(&state));
+        }
+    }
+
+    #[test]
+    fn test_evaluate_example_policy() {
+        let rules = Easy21Rules::default();
+        let reference = Reference::new(&rules);
+        for state in states(&rules) {
+            let v = reference.example_policy.v.get(&state);
+            assert!(v <= reference.optimal.v.get(&state) + 1e-9);
+            if state.player >= 20 {
+                assert_eq!(v, reference.example_policy.q.get(&state, &Action::Stick));
+            }
+        }
+    }
+
+    #[test]
+    fn test_evaluate_stochastic() {
+        let rules = Easy21Rules::default();
+        let mut rng = StdRng::seed_from_u64(0);
+        let deterministic = evaluate(&rules, |s| example_policy(&mut rng, s));
+        let stochastic = evaluate_stochastic(&rules, |s| match example_policy(&mut rng, s) {
+            Action::Hit => vec![1.0, 0.0],
+            Action::Stick => vec![0.0, 1.0],
+        });
+        assert!(max_diff(&deterministic.q, &stochastic.q) < 1e-9);
+
+        // The uniform policy, against the mean return of rollouts.
+        let uniform = evaluate_stochastic(&rules, |_| vec![0.5, 0.5]);
+        let start = State {
+            dealer: 5,
+            player: 15,
+        };
+        let episodes = 100_000;
+        let mut total = 0.0;
+        for _ in 0..episodes {
+            let mut state = start;
+            loop {
+                let action = Easy21Env::ACTIONS[rng.gen_range(0..2)];
+                let sample = step(&mut rng, &rules, state, action);
+                if sample.terminal {
+                    total += sample.reward;
+                    break;
+                }
+                state = sample.state;
+            }
+        }
+        let mean = total / episodes as f64;
+        let exact = uniform.v.get(&start);
+        assert!((mean - exact).abs() < 0.01, "{} {}", mean, exact);
+    }
+
+    #[test]
+    fn test_dealer_hits_below_min_sum() {
+        let rules = Easy21Rules {
+            min_sum: 3,
+            ..Default::default()
+        };
+        let dealer = Dealer::new(&rules);
+        let mut rng = StdRng::seed_from_u64(0);
+        let episodes = 100_000;
+        for dealer_card in [1, 2, 10] {
+            for player in [3, 15, 20] {
+                let state = State {
+                    dealer: dealer_card,
+                    player,
+                };
+                let mean = (0..episodes)
+                    .map(|_| step(&mut rng, &rules, state, Action::Stick).reward)
+                    .sum::<f64>()
+                    / episodes as f64;
+                let exact = dealer.stick(&state);
+                assert!(
+                    (mean - exact).abs() < 0.01,
+                    "{:?}: {} {}",
+                    state,
+                    mean,
+                    exact
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_player_first_card_outside_valid_sums() {
+        let rules = Easy21Rules {
+            min_sum: 5,
+            max_sum: 8,
+            dealer_sticks_at: 7,
+            ..Default::default()
+        };
+        let mut rng = StdRng::seed_from_u64(0);
+        let all: Vec<State> = states(&rules).collect();
+        for _ in 0..1000 {
+            assert!(all.contains(&State::init(&mut rng, &rules)));
+        }
+
+        // Hitting on a first card of 2, then sticking if the sum is valid.
+        let solution = evaluate(&rules, |_| Action::Stick);
+        let state = State {
+            dealer: 3,
+            player: 2,
+        };
+        let episodes = 100_000;
+        let mean = (0..episodes)
+            .map(|_| {
+                let sample = step(&mut rng, &rules, state, Action::Hit);
+                if sample.terminal {
+                    sample.reward
+                } else {
+                    step(&mut rng, &rules, sample.state, Action::Stick).reward
+                }
+            })
+            .sum::<f64>()
+            / episodes as f64;
+        let exact = solution.q.get(&state, &Action::Hit);
+        assert!((mean - exact).abs() < 0.01, "{} {}", mean, exact);
+    }
+
+    #[test]
+    fn test_sticking_on_21() {
+        let solution = value_iteration(&Easy21Rules::default());
+        for dealer in 1..=10 {
+            let q = solution
+                .q
+                .get(&State { dealer, player: 21 }, &Action::Stick);
+            assert!(q > 0.9 && q < 1.0, "{}", q);
+        }
+    }
+}
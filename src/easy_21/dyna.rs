@@ -0,0 +1,382 @@
+//! Dyna-Q: Q-learning that also learns a model of the environment from its real steps,
+//! and after every real step replays simulated steps of the model to update the same
+//! action values.
+//!
+//! The model is a table of every outcome seen after every state-action pair, with how
+//! often it occurred, so that it samples outcomes in proportion to their observed
+//! frequencies. Dyna-Q+ adds a bonus to the simulated rewards that grows with the time
+//! since the pair was last tried for real, which draws the agent back to pairs whose
+//! outcomes may have changed.
+//!
+//! Planning pays off most where outcomes are deterministic. In Easy21 a pair tried only
+//! once or twice has a model that is far from the true distribution, and planning drives
+//! its value to the mean of those few outcomes, so the error against Q* can start out
+//! higher than without planning.
+
+use rand::{rngs::StdRng, Rng};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    dp, exploration::Exploration, max_q, persistence, Action, Easy21Env, Easy21State, HasV,
+    Hyperparameters, State, Q,
+};
+use crate::env::{Sample, Tabular};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DynaMethod {
+    DynaQ,
+    /// With an exploration bonus on the simulated rewards.
+    DynaQPlus,
+}
+
+/// The outcomes seen after a state-action pair.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct Entry<S> {
+    /// Each distinct outcome and how often it occurred.
+    outcomes: Vec<(Sample<S>, i32)>,
+    /// The real step of the last try.
+    last_tried: i64,
+}
+
+impl<S> Default for Entry<S> {
+    fn default() -> Self {
+        Self {
+            outcomes: vec![],
+            last_tried: 0,
+        }
+    }
+}
+
+/// A simulated step of a [`Model`].
+pub struct Simulation<E: Tabular> {
+    pub state: E::State,
+    pub action: E::Action,
+    pub sample: Sample<E::State>,
+    /// The real step of the last try of `action` in `state`.
+    pub last_tried: i64,
+}
+
+/// A stochastic model of a tabular environment, learned from real steps.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(bound(
+    serialize = "E::State: Serialize, E::Action: Serialize",
+    deserialize = "E::State: Deserialize<'de>, E::Action: Deserialize<'de>"
+))]
+pub struct Model<E: Tabular> {
+    /// One entry per state-action pair, in the layout of [`Q`].
+    entries: Vec<Entry<E::State>>,
+    /// The state-action pairs tried so far, each once.
+    tried: Vec<(E::State, E::Action)>,
+}
+
+impl<E: Tabular> Default for Model<E> {
+    fn default() -> Self {
+        Self {
+            entries: vec![Entry::default(); E::STATES * E::ACTIONS.len()],
+            tried: vec![],
+        }
+    }
+}
+
+impl<E: Tabular> Model<E> {
+    fn index(state: &E::State, action: &E::Action) -> usize {
+        E::action_index(action) * E::STATES + E::state_index(state)
+    }
+
+    /// Whether the model has one entry per state-action pair, and every tried pair has
+    /// outcomes whose successors are within the tables, as a loaded model should.
+    pub fn fits(&self) -> bool {
+        let in_table = |state: &E::State| E::state_index(state) < E::STATES;
+        if self.entries.len() != E::STATES * E::ACTIONS.len() {
+            return false;
+        }
+        for (state, action) in &self.tried {
+            if !in_table(state) {
+                return false;
+            }
+            let outcomes = &self.entries[Self::index(state, action)].outcomes;
+            let valid = |(sample, count): &(Sample<E::State>, i32)| {
+                *count > 0 && (sample.terminal || in_table(&sample.state))
+            };
+            if outcomes.is_empty() || !outcomes.iter().all(valid) {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Counts the outcome `sample` of `action` in `state`, tried at real step `time`.
+    /// Terminal outcomes only differ in their reward.
+    pub fn record(
+        &mut self,
+        state: E::State,
+        action: E::Action,
+        sample: Sample<E::State>,
+        time: i64,
+    ) {
+        let entry = &mut self.entries[Self::index(&state, &action)];
+        if entry.outcomes.is_empty() {
+            self.tried.push((state, action));
+        }
+        entry.last_tried = time;
+        let same = |other: &Sample<E::State>| {
+            other.reward == sample.reward
+                && other.terminal == sample.terminal
+                && (sample.terminal
+                    || E::state_index(&other.state) == E::state_index(&sample.state))
+        };
+        match entry.outcomes.iter_mut().find(|(other, _)| same(other)) {
+            Some((_, count)) => *count += 1,
+            None => entry.outcomes.push((sample, 1)),
+        }
+    }
+
+    /// Picks a tried state-action pair uniformly, and one of its outcomes in proportion
+    /// to how often it occurred. Returns `None` if nothing has been tried yet.
+    pub fn simulate<R: Rng>(&self, rng: &mut R) -> Option<Simulation<E>> {
+        if self.tried.is_empty() {
+            return None;
+        }
+        let (state, action) = self.tried[rng.gen_range(0..self.tried.len())];
+        let entry = &self.entries[Self::index(&state, &action)];
+        let total: i32 = entry.outcomes.iter().map(|(_, count)| count).sum();
+        let mut u = rng.gen_range(0..total);
+        for (sample, count) in &entry.outcomes {
+            if u < *count {
+                return Some(Simulation {
+                    state,
+                    action,
+                    sample: *sample,
+                    last_tried: entry.last_tried,
+                });
+            }
+            u -= count;
+        }
+        unreachable!("the counts add up to the total")
+    }
+}
+
+pub struct DynaState<E: Tabular = Easy21Env> {
+    pub q: Q<(f64, i32), E>,
+    pub model: Model<E>,
+    pub episodes: i32,
+    /// Real steps so far, the clock of the exploration bonus.
+    pub steps: i64,
+    pub method: DynaMethod,
+    pub hyperparameters: Hyperparameters,
+}
+
+impl<E: Tabular> DynaState<E> {
+    /// Dyna-Q with an empty model. Set `method` for Dyna-Q+.
+    pub fn init(hyperparameters: Hyperparameters) -> Self {
+        Self {
+            q: Q::init((0.0, 0)),
+            model: Model::default(),
+            episodes: 0,
+            steps: 0,
+            method: DynaMethod::DynaQ,
+            hyperparameters,
+        }
+    }
+}
+
+impl HasV for DynaState {
+    fn get_v(&self, state: &State) -> f64 {
+        max_q(&self.q, state)
+    }
+}
+
+impl Easy21State for DynaState {
+    fn update(&mut self, env: &Easy21Env, rng: &mut StdRng) {
+        let exploration = self.hyperparameters.exploration;
+        dyna_q(env, rng, &exploration, self);
+    }
+    fn episodes(&self) -> i32 {
+        self.episodes
+    }
+    fn policy(&self, state: &State) -> Action {
+        if self.q.get(state, &Action::Hit) > self.q.get(state, &Action::Stick) {
+            Action::Hit
+        } else {
+            Action::Stick
+        }
+    }
+    fn rms_error(&self, reference: &dp::Reference) -> f64 {
+        self.q.rms_error(&reference.optimal)
+    }
+    fn set_hyperparameters(&mut self, h: Hyperparameters) {
+        self.hyperparameters = h;
+    }
+    fn save(&self) -> persistence::Learner {
+        let q = self.q.clone();
+        let model = self.model.clone();
+        let steps = self.steps;
+        let episodes = self.episodes;
+        let hyperparameters = self.hyperparameters;
+        match self.method {
+            DynaMethod::DynaQ => persistence::Learner::DynaQ {
+                q,
+                model,
+                steps,
+                episodes,
+                hyperparameters,
+            },
+            DynaMethod::DynaQPlus => persistence::Learner::DynaQPlus {
+                q,
+                model,
+                steps,
+                episodes,
+                hyperparameters,
+            },
+        }
+    }
+}
+
+/// Moves the value of `action` in `state` towards `reward` plus the greedy value of the
+/// successor in `sample`, by `alpha`. Leaves the count alone.
+fn q_update<E: Tabular>(
+    q: &mut Q<(f64, i32), E>,
+    state: &E::State,
+    action: &E::Action,
+    reward: f64,
+    sample: &Sample<E::State>,
+    alpha: f64,
+) {
+    let next_value = if sample.terminal {
+        0.0
+    } else {
+        max_q(q, &sample.state)
+    };
+    q.update(state, action, |(v, n)| {
+        (v + alpha * (reward + next_value - v), *n)
+    });
+}
+
+/// One episode of Dyna-Q: a Q-learning update after every real step, then
+/// `planning_steps` more from simulated steps of the model. Only the real updates count
+/// towards the step sizes and the visit counts of the exploration schedule.
+pub fn dyna_q<E: Tabular, R: Rng, X: Exploration<E>>(
+    env: &E,
+    rng: &mut R,
+    exploration: &X,
+    dyna_state: &mut DynaState<E>,
+) {
+    let h = dyna_state.hyperparameters;
+    let mut state = env.reset(rng);
+    loop {
+        let action = exploration.choose(rng, &dyna_state.q, &state);
+        let sample = env.step(rng, state, action);
+
+        let alpha = h.step_size.at(dyna_state.q.get(&state, &action).1);
+        q_update(
+            &mut dyna_state.q,
+            &state,
+            &action,
+            sample.reward,
+            &sample,
+            alpha,
+        );
+        dyna_state.q.update(&state, &action, |(v, n)| (*v, n + 1));
+        dyna_state.steps += 1;
+        dyna_state
+            .model
+            .record(state, action, sample, dyna_state.steps);
+
+        for _ in 0..h.planning_steps {
+            let Some(simulation) = dyna_state.model.simulate(rng) else {
+                break;
+            };
+            let bonus = match dyna_state.method {
+                DynaMethod::DynaQ => 0.0,
+                DynaMethod::DynaQPlus => {
+                    let elapsed = dyna_state.steps - simulation.last_tried;
+                    h.exploration_bonus * (elapsed as f64).sqrt()
+                }
+            };
+            let Simulation {
+                state,
+                action,
+                sample,
+                ..
+            } = simulation;
+            let alpha = h.step_size.at(dyna_state.q.get(&state, &action).1);
+            let reward = sample.reward + bonus;
+            q_update(&mut dyna_state.q, &state, &action, reward, &sample, alpha);
+        }
+
+        if sample.terminal {
+            break;
+        }
+        state = sample.state;
+    }
+    dyna_state.episodes += 1;
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::easy_21::Algorithm;
+    use crate::env::Corridor;
+
+    #[test]
+    fn test_planning() {
+        // Real steps of the first episodes.
+        let steps = |method, planning_steps| {
+            let h = Hyperparameters {
+                planning_steps,
+                ..Algorithm::DynaQ.hyperparameters()
+            };
+            let mut rng = StdRng::seed_from_u64(0);
+            let mut dyna_state = DynaState::<Corridor<10>> {
+                method,
+                ..DynaState::init(h)
+            };
+            for _ in 0..10 {
+                dyna_q(&Corridor, &mut rng, &h.exploration, &mut dyna_state);
+            }
+            dyna_state.steps
+        };
+        let q_learning = steps(DynaMethod::DynaQ, 0);
+        for method in [DynaMethod::DynaQ, DynaMethod::DynaQPlus] {
+            let dyna = steps(method, 20);
+            assert!(
+                2 * dyna < q_learning,
+                "{:?}: {} {}",
+                method,
+                dyna,
+                q_learning
+            );
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let env = Easy21Env::default();
+        let h = Algorithm::DynaQPlus.hyperparameters();
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut dyna_state = DynaState {
+            method: DynaMethod::DynaQPlus,
+            ..DynaState::init(h)
+        };
+        for _ in 0..100 {
+            dyna_state.update(&env, &mut rng);
+        }
+        // With the model and the clock, a loaded learner goes on like the original.
+        let format = persistence::Format::Binary;
+        let snapshot = persistence::Snapshot::new(env.rules, 0, dyna_state.save());
+        let snapshot = format.decode(&format.encode(&snapshot)).unwrap();
+        let mut loaded = snapshot.learner.into_state();
+        let mut loaded_rng = rng.clone();
+        for _ in 0..100 {
+            dyna_state.update(&env, &mut rng);
+            loaded.update(&env, &mut loaded_rng);
+        }
+        let reference = dp::Reference::new(&env.rules);
+        assert_eq!(
+            loaded.rms_error(&reference),
+            dyna_state.rms_error(&reference)
+        );
+    }
+}
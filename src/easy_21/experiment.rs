@@ -0,0 +1,319 @@
+//! The λ sweep of the Easy21 assignment: train SARSA(λ) and linear SARSA(λ) for a fixed
+//! number of episodes at λ = 0, 0.1, …, 1 and compare the mean squared error against Q*.
+
+use plotters::prelude::*;
+use rand::{rngs::StdRng, SeedableRng};
+
+use super::{
+    approx_td_lambda_control, dp::Solution, td_lambda_control, Algorithm, ApproxState, Easy21Env,
+    TDControlState,
+};
+
+const LAMBDAS: usize = 11;
+
+fn lambda(i: usize) -> f64 {
+    i as f64 / (LAMBDAS - 1) as f64
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Method {
+    Tabular,
+    Linear,
+}
+
+impl Method {
+    fn name(&self) -> &'static str {
+        match self {
+            Method::Tabular => "SARSA(λ)",
+            Method::Linear => "Linear SARSA(λ)",
+        }
+    }
+
+    fn color(&self) -> RGBColor {
+        match self {
+            Method::Tabular => BLACK,
+            Method::Linear => BLUE,
+        }
+    }
+}
+
+/// The learner of the run in progress.
+enum Learner {
+    Tabular(TDControlState),
+    Linear(ApproxState),
+}
+
+impl Learner {
+    fn init(method: Method, lambda: f64) -> Self {
+        match method {
+            Method::Tabular => {
+                let h = Algorithm::TDLambdaControl.hyperparameters();
+                Learner::Tabular(TDControlState::init(super::Hyperparameters { lambda, ..h }))
+            }
+            Method::Linear => {
+                let h = Algorithm::ApproxTDLambdaControl.hyperparameters();
+                Learner::Linear(ApproxState::init(
+                    super::Hyperparameters { lambda, ..h },
+                    h.features,
+                ))
+            }
+        }
+    }
+}
+
+struct Run {
+    method: Method,
+    lambda: f64,
+    /// Mean squared error against Q* after each episode.
+    curve: Vec<f64>,
+}
+
+/// Trains one learner per method and λ, a few episodes per frame.
+pub struct LambdaSweep {
+    /// Episodes per run, for the next sweep.
+    pub episodes: usize,
+    /// Which λ values get their learning curves plotted.
+    pub curves: [bool; LAMBDAS],
+    env: Easy21Env,
+    optimal: Option<Solution>,
+    seed: u64,
+    /// Episodes per run of the current sweep, fixed when it starts.
+    run_episodes: usize,
+    runs: Vec<Run>,
+    learner: Option<(Learner, StdRng)>,
+}
+
+impl Default for LambdaSweep {
+    fn default() -> Self {
+        let mut curves = [false; LAMBDAS];
+        curves[0] = true;
+        curves[LAMBDAS - 1] = true;
+        Self {
+            episodes: 1000,
+            curves,
+            env: Easy21Env::default(),
+            optimal: None,
+            seed: 0,
+            run_episodes: 0,
+            runs: vec![],
+            learner: None,
+        }
+    }
+}
+
+impl LambdaSweep {
+    /// Discards previous results and schedules all runs. Every run starts from the same
+    /// seed, so differences between runs come from λ alone.
+    pub fn start(&mut self, env: Easy21Env, optimal: Solution, seed: u64) {
+        self.env = env;
+        self.optimal = Some(optimal);
+        self.seed = seed;
+        self.run_episodes = self.episodes;
+        self.learner = None;
+        self.runs = [Method::Tabular, Method::Linear]
+            .into_iter()
+            .flat_map(|method| {
+                (0..LAMBDAS).map(move |i| Run {
+                    method,
+                    lambda: lambda(i),
+                    curve: vec![],
+                })
+            })
+            .collect();
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.runs
+            .iter()
+            .any(|run| run.curve.len() < self.run_episodes)
+    }
+
+    /// Trains until `budget` has passed or all runs are done.
+    pub fn update(&mut self, budget: web_time::Duration) {
+        let Some(optimal) = &self.optimal else {
+            return;
+        };
+        let start_time = web_time::Instant::now();
+        while start_time.elapsed() < budget {
+            let Some(run) = self
+                .runs
+                .iter_mut()
+                .find(|run| run.curve.len() < self.run_episodes)
+            else {
+                break;
+            };
+            let (learner, rng) = self.learner.get_or_insert_with(|| {
+                (
+                    Learner::init(run.method, run.lambda),
+                    StdRng::seed_from_u64(self.seed),
+                )
+            });
+            let mse = match learner {
+                Learner::Tabular(state) => {
+                    let exploration = state.hyperparameters.exploration;
+                    td_lambda_control(&self.env, rng, &exploration, state);
+                    optimal.mean_squared_error(&state.q)
+                }
+                Learner::Linear(state) => {
+                    let exploration = state.hyperparameters.exploration;
+                    approx_td_lambda_control(&self.env, rng, &exploration, state);
+                    optimal.mean_squared_error(state)
+                }
+            };
+            run.curve.push(mse);
+            if run.curve.len() >= self.run_episodes {
+                self.learner = None;
+            }
+        }
+    }
+
+    /// Shows the controls and plots. Runs started from here use the given environment,
+    /// reference solution and seed.
+    pub fn ui(&mut self, ui: &mut egui::Ui, env: &Easy21Env, optimal: &Solution, seed: u64) {
+        ui.horizontal(|ui| {
+            ui.label("Episodes per run:");
+            ui.add(egui::DragValue::new(&mut self.episodes).clamp_range(1..=1_000_000));
+            if ui.button("Run").clicked() {
+                self.start(*env, optimal.clone(), seed);
+            }
+        });
+
+        let done = self
+            .runs
+            .iter()
+            .filter(|run| run.curve.len() >= self.run_episodes);
+        ui.label(format!("Runs done: {} / {}", done.count(), self.runs.len()));
+
+        ui.horizontal(|ui| {
+            ui.label("Curves for λ:");
+            for (i, shown) in self.curves.iter_mut().enumerate() {
+                ui.checkbox(shown, format!("{:.1}", lambda(i)));
+            }
+        });
+
+        let size = egui::vec2(ui.available_width().max(400.0), 300.0);
+        let (rect, _) = ui.allocate_exact_size(size, egui::Sense::hover());
+        let (left, right) = rect.split_left_right_at_fraction(0.5);
+        self.draw_final_errors(&ui.child_ui(left, egui::Layout::default()));
+        self.draw_curves(&ui.child_ui(right, egui::Layout::default()));
+    }
+
+    /// Mean squared error after the last episode, against λ.
+    fn draw_final_errors(&self, ui: &egui::Ui) {
+        let area = egui_plotter::EguiBackend::new(ui).into_drawing_area();
+        let finished = |method| {
+            self.runs
+                .iter()
+                .filter(move |run| run.method == method && run.curve.len() >= self.run_episodes)
+                .map(|run| (run.lambda, *run.curve.last().unwrap()))
+        };
+        let max = self
+            .runs
+            .iter()
+            .filter_map(|run| run.curve.last())
+            .fold(0.01, |a: f64, b| a.max(*b));
+
+        let mut chart = ChartBuilder::on(&area)
+            .caption("MSE vs. λ", ("sans-serif", 14))
+            .margin(10)
+            .x_label_area_size(20)
+            .y_label_area_size(40)
+            .build_cartesian_2d(0.0..1.0, 0.0..max)
+            .unwrap();
+
+        chart.configure_mesh().draw().unwrap();
+
+        for method in [Method::Tabular, Method::Linear] {
+            let color = method.color();
+            chart
+                .draw_series(LineSeries::new(finished(method), &color))
+                .unwrap()
+                .label(method.name())
+                .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 15, y)], color));
+        }
+
+        chart
+            .configure_series_labels()
+            .background_style(WHITE.mix(0.8))
+            .border_style(BLACK)
+            .draw()
+            .unwrap();
+    }
+
+    /// Mean squared error after each episode, for the selected λ values.
+    fn draw_curves(&self, ui: &egui::Ui) {
+        let area = egui_plotter::EguiBackend::new(ui).into_drawing_area();
+        let shown = |run: &&Run| {
+            let i = (run.lambda * (LAMBDAS - 1) as f64).round() as usize;
+            self.curves[i]
+        };
+        let max = self
+            .runs
+            .iter()
+            .filter(shown)
+            .flat_map(|run| run.curve.iter())
+            .fold(0.01, |a: f64, b| a.max(*b));
+
+        let mut chart = ChartBuilder::on(&area)
+            .caption("Learning curves", ("sans-serif", 14))
+            .margin(10)
+            .x_label_area_size(20)
+            .y_label_area_size(40)
+            .build_cartesian_2d(0.0..self.run_episodes as f64, 0.0..max)
+            .unwrap();
+
+        chart.configure_mesh().draw().unwrap();
+
+        for (i, run) in self.runs.iter().filter(shown).enumerate() {
+            let color = Palette99::pick(i).to_rgba();
+            let points = run
+                .curve
+                .iter()
+                .enumerate()
+                .map(|(episode, mse)| (episode as f64 + 1.0, *mse));
+            chart
+                .draw_series(LineSeries::new(points, &color))
+                .unwrap()
+                .label(format!("{} λ={:.1}", run.method.name(), run.lambda))
+                .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 15, y)], color));
+        }
+
+        chart
+            .configure_series_labels()
+            .background_style(WHITE.mix(0.8))
+            .border_style(BLACK)
+            .draw()
+            .unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::easy_21::dp;
+
+    #[test]
+    fn test_sweep() {
+        let env = Easy21Env::default();
+        let mut sweep = LambdaSweep {
+            episodes: 5,
+            ..Default::default()
+        };
+        sweep.start(env, dp::value_iteration(&env.rules), 0);
+        // Editing the episodes mid-sweep only affects the next one.
+        sweep.episodes = 50;
+        while sweep.is_running() {
+            sweep.update(web_time::Duration::from_millis(10));
+        }
+        for method in [Method::Tabular, Method::Linear] {
+            let lambdas: Vec<f64> = sweep
+                .runs
+                .iter()
+                .filter(|run| run.method == method)
+                .map(|run| run.lambda)
+                .collect();
+            assert_eq!(lambdas, (0..LAMBDAS).map(lambda).collect::<Vec<_>>());
+        }
+        assert!(sweep.runs.iter().all(|run| run.curve.len() == 5));
+    }
+}
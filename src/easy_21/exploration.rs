@@ -0,0 +1,412 @@
+//! How control algorithms trade off exploring against exploiting their action values.
+//!
+//! An [`Exploration`] strategy turns the action values of a state into a distribution
+//! over actions. Schedules that decay with experience read the visit counts through
+//! [`Counted`], which the tabular learners get from the counts they already keep next to
+//! every action value.
+
+use std::ops::RangeInclusive;
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use super::{epsilon_greedy, greedy, HasQ, Q};
+use crate::env::{Environment, Tabular};
+
+/// Action values together with how often each action has been taken.
+pub trait Counted<E: Environment>: HasQ<E> {
+    /// Number of updates of the value of `action` in `state`.
+    fn count(&self, state: &E::State, action: &E::Action) -> i32;
+
+    /// Number of visits of `state`.
+    fn visits(&self, state: &E::State) -> i32 {
+        E::ACTIONS.iter().map(|a| self.count(state, a)).sum()
+    }
+}
+
+impl<E: Tabular> Counted<E> for Q<(f64, i32), E> {
+    fn count(&self, state: &E::State, action: &E::Action) -> i32 {
+        self.get(state, action).1
+    }
+}
+
+/// The second component is a weight rather than a count, so off-policy Monte Carlo sees
+/// every state as unvisited.
+impl<E: Tabular> Counted<E> for Q<(f64, f64), E> {
+    fn count(&self, _state: &E::State, _action: &E::Action) -> i32 {
+        0
+    }
+}
+
+/// A policy derived from action values.
+pub trait Exploration<E: Environment> {
+    /// Probability of each action in `state`, in the order of `E::ACTIONS`.
+    fn probabilities<Q: Counted<E>>(&self, q: &Q, state: &E::State) -> Vec<f64>;
+
+    /// Samples an action in `state`.
+    fn choose<R: Rng, Q: Counted<E>>(&self, rng: &mut R, q: &Q, state: &E::State) -> E::Action {
+        let probabilities = self.probabilities(q, state);
+        let mut u = rng.gen::<f64>();
+        for (action, p) in E::ACTIONS.iter().zip(&probabilities) {
+            if u < *p {
+                return *action;
+            }
+            u -= p;
+        }
+        // Rounding left a little probability over.
+        let last = probabilities.iter().rposition(|p| *p > 0.0).unwrap_or(0);
+        E::ACTIONS[last]
+    }
+
+    /// Probability of taking `action` in `state`.
+    fn probability<Q: Counted<E>>(&self, q: &Q, state: &E::State, action: &E::Action) -> f64 {
+        let i = E::ACTIONS.iter().position(|a| a == action).unwrap();
+        self.probabilities(q, state)[i]
+    }
+
+    /// Expected action value in `state` when acting with this strategy.
+    fn expected_q<Q: Counted<E>>(&self, q: &Q, state: &E::State) -> f64 {
+        E::ACTIONS
+            .iter()
+            .zip(self.probabilities(q, state))
+            .map(|(a, p)| p * q.get_q(state, a))
+            .sum()
+    }
+}
+
+/// A parameter that changes with the number of visits `n` of a state.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Schedule {
+    Constant(f64),
+    /// `1 / (offset + n / visits)`.
+    Harmonic {
+        offset: f64,
+        visits: f64,
+    },
+    /// `start * 0.5^(n / half_life)`.
+    Exponential {
+        start: f64,
+        half_life: f64,
+    },
+}
+
+impl Schedule {
+    pub fn at(&self, n: i32) -> f64 {
+        match *self {
+            Schedule::Constant(value) => value,
+            Schedule::Harmonic { offset, visits } => 1.0 / (offset + n as f64 / visits),
+            Schedule::Exponential { start, half_life } => start * 0.5f64.powf(n as f64 / half_life),
+        }
+    }
+
+    /// Shows the kind of schedule and its settings as rows of a two-column grid, where
+    /// `name` is the parameter and `range` its sensible values. Without `counted` visits,
+    /// only constant schedules are offered. Returns whether anything changed.
+    fn ui(
+        &mut self,
+        ui: &mut egui::Ui,
+        name: &str,
+        range: RangeInclusive<f64>,
+        counted: bool,
+    ) -> bool {
+        let mut changed = false;
+        let logarithmic = *range.start() > 0.0;
+        ui.label(format!("{} schedule:", name));
+        ui.horizontal(|ui| {
+            let start = self.at(0);
+            let kinds = [
+                ("Constant", Schedule::Constant(start)),
+                (
+                    "1 / (c + n / s)",
+                    Schedule::Harmonic {
+                        offset: 10.0,
+                        visits: 10_000.0,
+                    },
+                ),
+                (
+                    "Exponential",
+                    Schedule::Exponential {
+                        start,
+                        half_life: 10_000.0,
+                    },
+                ),
+            ];
+            let kinds = kinds
+                .into_iter()
+                .filter(|(_, schedule)| counted || matches!(schedule, Schedule::Constant(_)));
+            for (label, schedule) in kinds {
+                let selected = std::mem::discriminant(self) == std::mem::discriminant(&schedule);
+                if ui.selectable_label(selected, label).clicked() && !selected {
+                    *self = schedule;
+                    changed = true;
+                }
+            }
+        });
+        ui.end_row();
+        if !counted && !matches!(self, Schedule::Constant(_)) {
+            ui.label("");
+            ui.label(format!(
+                "This learner keeps no visit counts, so {} stays at its start.",
+                name
+            ));
+            ui.end_row();
+        }
+
+        match self {
+            Schedule::Constant(value) => {
+                ui.label(format!("{}:", name));
+                changed |= ui
+                    .add(egui::Slider::new(value, range).logarithmic(logarithmic))
+                    .changed();
+                ui.end_row();
+            }
+            Schedule::Harmonic { offset, visits } => {
+                ui.label(format!("{} offset c:", name));
+                changed |= ui
+                    .add(egui::Slider::new(offset, 1.0..=100.0).logarithmic(true))
+                    .changed();
+                ui.end_row();
+
+                ui.label(format!("{} visits s:", name));
+                changed |= ui
+                    .add(egui::Slider::new(visits, 1.0..=1e7).logarithmic(true))
+                    .changed();
+                ui.end_row();
+            }
+            Schedule::Exponential { start, half_life } => {
+                ui.label(format!("Initial {}:", name));
+                changed |= ui
+                    .add(egui::Slider::new(start, range).logarithmic(logarithmic))
+                    .changed();
+                ui.end_row();
+
+                ui.label("Half-life (visits):");
+                changed |= ui
+                    .add(egui::Slider::new(half_life, 1.0..=1e7).logarithmic(true))
+                    .changed();
+                ui.end_row();
+            }
+        }
+        changed
+    }
+}
+
+/// Takes a uniformly random action with probability ε, and the greedy action otherwise.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EpsilonGreedy(pub Schedule);
+
+impl<E: Environment> Exploration<E> for EpsilonGreedy {
+    fn probabilities<Q: Counted<E>>(&self, q: &Q, state: &E::State) -> Vec<f64> {
+        let eps = self.0.at(q.visits(state));
+        let best = greedy(q, state);
+        let explore = eps / E::ACTIONS.len() as f64;
+        E::ACTIONS
+            .iter()
+            .map(|a| {
+                if *a == best {
+                    1.0 - eps + explore
+                } else {
+                    explore
+                }
+            })
+            .collect()
+    }
+
+    fn choose<R: Rng, Q: Counted<E>>(&self, rng: &mut R, q: &Q, state: &E::State) -> E::Action {
+        epsilon_greedy(rng, self.0.at(q.visits(state)), q, state)
+    }
+}
+
+/// Softmax over the action values divided by a temperature τ. High temperatures act
+/// almost uniformly, low ones almost greedily.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Boltzmann(pub Schedule);
+
+impl<E: Environment> Exploration<E> for Boltzmann {
+    fn probabilities<Q: Counted<E>>(&self, q: &Q, state: &E::State) -> Vec<f64> {
+        let temperature = self.0.at(q.visits(state)).max(1e-6);
+        let values: Vec<f64> = E::ACTIONS.iter().map(|a| q.get_q(state, a)).collect();
+        // Shifting by the maximum keeps the exponentials finite.
+        let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        let weights: Vec<f64> = values
+            .iter()
+            .map(|v| ((v - max) / temperature).exp())
+            .collect();
+        let sum: f64 = weights.iter().sum();
+        weights.iter().map(|w| w / sum).collect()
+    }
+}
+
+/// Upper confidence bounds: greedy with respect to `q + c sqrt(ln N / n)`, where `n`
+/// counts the action in the state and `N` the visits of the state. Untried actions come
+/// first. Deterministic, so learners without counts act greedily.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Ucb(pub f64);
+
+impl Ucb {
+    fn best<E: Environment, Q: Counted<E>>(&self, q: &Q, state: &E::State) -> E::Action {
+        let visits = q.visits(state);
+        if visits == 0 {
+            return greedy(q, state);
+        }
+        let log_visits = (visits as f64).ln();
+        let bound = |a: &E::Action| {
+            let n = q.count(state, a);
+            if n == 0 {
+                f64::INFINITY
+            } else {
+                q.get_q(state, a) + self.0 * (log_visits / n as f64).sqrt()
+            }
+        };
+        let mut best = E::ACTIONS[0];
+        let mut best_bound = bound(&best);
+        for action in &E::ACTIONS[1..] {
+            let b = bound(action);
+            if b >= best_bound {
+                best = *action;
+                best_bound = b;
+            }
+        }
+        best
+    }
+}
+
+impl<E: Environment> Exploration<E> for Ucb {
+    fn probabilities<Q: Counted<E>>(&self, q: &Q, state: &E::State) -> Vec<f64> {
+        let best = self.best(q, state);
+        E::ACTIONS
+            .iter()
+            .map(|a| if *a == best { 1.0 } else { 0.0 })
+            .collect()
+    }
+
+    fn choose<R: Rng, Q: Counted<E>>(&self, _rng: &mut R, q: &Q, state: &E::State) -> E::Action {
+        self.best(q, state)
+    }
+}
+
+/// One of the strategies above, as chosen in the hyperparameters.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Strategy {
+    EpsilonGreedy(Schedule),
+    /// The schedule gives the temperature.
+    Boltzmann(Schedule),
+    /// The factor `c` of the exploration bonus.
+    Ucb(f64),
+}
+
+impl Default for Strategy {
+    fn default() -> Self {
+        Strategy::EpsilonGreedy(Schedule::Harmonic {
+            offset: 10.0,
+            visits: 10_000.0,
+        })
+    }
+}
+
+impl<E: Environment> Exploration<E> for Strategy {
+    fn probabilities<Q: Counted<E>>(&self, q: &Q, state: &E::State) -> Vec<f64> {
+        match *self {
+            Strategy::EpsilonGreedy(schedule) => EpsilonGreedy(schedule).probabilities(q, state),
+            Strategy::Boltzmann(schedule) => Boltzmann(schedule).probabilities(q, state),
+            Strategy::Ucb(c) => Ucb(c).probabilities(q, state),
+        }
+    }
+
+    fn choose<R: Rng, Q: Counted<E>>(&self, rng: &mut R, q: &Q, state: &E::State) -> E::Action {
+        match *self {
+            Strategy::EpsilonGreedy(schedule) => EpsilonGreedy(schedule).choose(rng, q, state),
+            Strategy::Boltzmann(schedule) => Boltzmann(schedule).choose(rng, q, state),
+            Strategy::Ucb(c) => Ucb(c).choose(rng, q, state),
+        }
+    }
+}
+
+impl Strategy {
+    /// Shows the strategy and its settings as rows of a two-column grid, and returns
+    /// whether anything changed. Without `counted` visits, as for learners whose
+    /// [`Counted::count`] is always 0, UCB and decaying schedules are not offered.
+    pub fn ui(&mut self, ui: &mut egui::Ui, counted: bool) -> bool {
+        let mut changed = false;
+        ui.label("Exploration:");
+        ui.horizontal(|ui| {
+            let schedule = match *self {
+                Strategy::EpsilonGreedy(schedule) | Strategy::Boltzmann(schedule) => schedule,
+                Strategy::Ucb(_) => Schedule::Constant(0.1),
+            };
+            let kinds = [
+                ("ε-greedy", Strategy::EpsilonGreedy(schedule)),
+                ("Boltzmann", Strategy::Boltzmann(schedule)),
+                ("UCB", Strategy::Ucb(1.0)),
+            ];
+            let kinds = kinds
+                .into_iter()
+                .filter(|(_, strategy)| counted || !matches!(strategy, Strategy::Ucb(_)));
+            for (label, strategy) in kinds {
+                let selected = std::mem::discriminant(self) == std::mem::discriminant(&strategy);
+                if ui.selectable_label(selected, label).clicked() && !selected {
+                    *self = strategy;
+                    changed = true;
+                }
+            }
+        });
+        ui.end_row();
+        if !counted && matches!(self, Strategy::Ucb(_)) {
+            ui.label("");
+            ui.label("This learner keeps no visit counts, so UCB acts greedily.");
+            ui.end_row();
+        }
+
+        match self {
+            Strategy::EpsilonGreedy(schedule) => {
+                changed |= schedule.ui(ui, "ε", 0.0..=1.0, counted)
+            }
+            Strategy::Boltzmann(schedule) => changed |= schedule.ui(ui, "τ", 0.001..=10.0, counted),
+            Strategy::Ucb(c) => {
+                ui.label("c:");
+                changed |= ui.add(egui::Slider::new(c, 0.0..=10.0)).changed();
+                ui.end_row();
+            }
+        }
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::easy_21::{Action, Easy21Env, State};
+
+    #[test]
+    fn test_probabilities() {
+        let state = State {
+            dealer: 5,
+            player: 15,
+        };
+        let mut q: Q<(f64, i32)> = Q::init((0.0, 0));
+        q.set(&state, &Action::Hit, (1.0, 3));
+        q.set(&state, &Action::Stick, (0.0, 1));
+        let hit = |strategy: Strategy, q: &Q<(f64, i32)>| {
+            let probabilities = Exploration::<Easy21Env>::probabilities(&strategy, q, &state);
+            assert!((probabilities.iter().sum::<f64>() - 1.0).abs() < 1e-12);
+            Exploration::<Easy21Env>::probability(&strategy, q, &state, &Action::Hit)
+        };
+
+        // ε = 1 / (2 + 4 / 4) after four visits.
+        let harmonic = Schedule::Harmonic {
+            offset: 2.0,
+            visits: 4.0,
+        };
+        assert!((hit(Strategy::EpsilonGreedy(harmonic), &q) - 5.0 / 6.0).abs() < 1e-12);
+        let e = 1f64.exp();
+        assert!(
+            (hit(Strategy::Boltzmann(Schedule::Constant(1.0)), &q) - e / (e + 1.0)).abs() < 1e-12
+        );
+        // The bonus of the rarely taken action outweighs its lower value once c is large.
+        assert_eq!(hit(Strategy::Ucb(1.0), &q), 1.0);
+        assert_eq!(hit(Strategy::Ucb(3.0), &q), 0.0);
+
+        q.set(&state, &Action::Stick, (0.0, 0));
+        assert_eq!(hit(Strategy::Ucb(0.0), &q), 0.0);
+    }
+}
@@ -0,0 +1,389 @@
+//! Feature vectors of state-action pairs for the linear learners.
+//!
+//! Extractors only list the features that are active in a state-action pair, so that
+//! evaluating and updating the action values costs time in the number of active features
+//! rather than in the length of the weight vector.
+//!
+//! Every extractor also describes states alone, for the linear prediction of state
+//! values, and gives each action its own block of these state features, so that the
+//! value of an action only depends on the weights of its block. Tile coding and radial
+//! basis functions cover the states of the default rules, dealer cards 1 to 10 and player
+//! sums 1 to 21. States of other rules outside that area get the features of the nearest
+//! point on its border.
+
+use serde::{Deserialize, Serialize};
+
+use super::{Action, Easy21Env, SparseVector, State, Vector};
+use crate::env::{Environment, Tabular};
+
+/// Maps state-action pairs to feature vectors of a fixed length.
+pub trait FeatureExtractor<E: Environment> {
+    /// Length of the feature vectors.
+    fn dimension(&self) -> usize;
+
+    /// Calls `f` with the index and value of every non-zero feature of the pair.
+    fn for_each<F: FnMut(usize, f64)>(&self, state: &E::State, action: &E::Action, f: F);
+
+    fn extract(&self, state: &E::State, action: &E::Action) -> SparseVector {
+        let mut entries = vec![];
+        self.for_each(state, action, |i, x| entries.push((i, x)));
+        SparseVector { entries }
+    }
+
+    /// The dot product of the features of the pair with `weights`.
+    fn value(&self, state: &E::State, action: &E::Action, weights: &Vector) -> f64 {
+        let mut sum = 0.0;
+        self.for_each(state, action, |i, x| sum += x * weights.w[i]);
+        sum
+    }
+}
+
+/// Maps states to feature vectors of a fixed length, for linear state values. The
+/// methods are named apart from those of [`FeatureExtractor`], since the extractors
+/// implement both.
+pub trait StateFeatureExtractor<E: Environment> {
+    /// Length of the feature vectors.
+    fn state_dimension(&self) -> usize;
+
+    /// Calls `f` with the index and value of every non-zero feature of the state.
+    fn for_each_state<F: FnMut(usize, f64)>(&self, state: &E::State, f: F);
+
+    fn extract_state(&self, state: &E::State) -> SparseVector {
+        let mut entries = vec![];
+        self.for_each_state(state, |i, x| entries.push((i, x)));
+        SparseVector { entries }
+    }
+
+    /// The dot product of the features of the state with `weights`.
+    fn state_value(&self, state: &E::State, weights: &Vector) -> f64 {
+        let mut sum = 0.0;
+        self.for_each_state(state, |i, x| sum += x * weights.w[i]);
+        sum
+    }
+}
+
+/// The state features in the block of `action`, one block per action.
+fn for_each_in_block<E, X, F>(extractor: &X, state: &E::State, action: &E::Action, mut f: F)
+where
+    E: Tabular,
+    X: StateFeatureExtractor<E>,
+    F: FnMut(usize, f64),
+{
+    let block = E::action_index(action) * extractor.state_dimension();
+    extractor.for_each_state(state, |i, x| f(block + i, x));
+}
+
+/// Number of state features of [`Cuboid`].
+const CUBOID_FEATURES: usize = 18;
+
+/// The coding of the original assignment: 3 × 6 overlapping cuboids of dealer cards and
+/// player sums. Unlike the other extractors, the action features interleave the actions
+/// within each cuboid, as in the assignment.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Cuboid;
+
+impl StateFeatureExtractor<Easy21Env> for Cuboid {
+    fn state_dimension(&self) -> usize {
+        CUBOID_FEATURES
+    }
+
+    fn for_each_state<F: FnMut(usize, f64)>(&self, state: &State, mut f: F) {
+        let mut i = 0;
+        for dealer_interval in &[1..=4, 4..=7, 7..=10] {
+            for player_interval in &[1..=6, 4..=9, 7..=12, 10..=15, 13..=18, 16..=21] {
+                let in_dealer = dealer_interval.contains(&state.dealer);
+                let in_player = player_interval.contains(&state.player);
+                if in_dealer && in_player {
+                    f(i, 1.0);
+                }
+                i += 1;
+            }
+        }
+    }
+}
+
+impl FeatureExtractor<Easy21Env> for Cuboid {
+    fn dimension(&self) -> usize {
+        Easy21Env::ACTIONS.len() * self.state_dimension()
+    }
+
+    fn for_each<F: FnMut(usize, f64)>(&self, state: &State, action: &Action, mut f: F) {
+        let actions = Easy21Env::ACTIONS.len();
+        let a = Easy21Env::action_index(action);
+        self.for_each_state(state, |i, x| f(i * actions + a, x));
+    }
+}
+
+/// One feature per state, in the layout of [`V`](super::V), and one per state-action
+/// pair, in the layout of [`Q`](super::Q). A linear learner with these features learns
+/// exactly like its tabular counterpart with the same constant step size.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OneHot;
+
+impl<E: Tabular> StateFeatureExtractor<E> for OneHot {
+    fn state_dimension(&self) -> usize {
+        E::STATES
+    }
+
+    fn for_each_state<F: FnMut(usize, f64)>(&self, state: &E::State, mut f: F) {
+        f(E::state_index(state), 1.0);
+    }
+}
+
+impl<E: Tabular> FeatureExtractor<E> for OneHot {
+    fn dimension(&self) -> usize {
+        E::STATES * E::ACTIONS.len()
+    }
+
+    fn for_each<F: FnMut(usize, f64)>(&self, state: &E::State, action: &E::Action, f: F) {
+        for_each_in_block::<E, _, _>(self, state, action, f);
+    }
+}
+
+/// Position of `state` in the unit square spanned by the states of the default rules.
+fn coordinates(state: &State) -> (f64, f64) {
+    let dealer = (state.dealer - 1) as f64 / 9.0;
+    let player = (state.player - 1) as f64 / 20.0;
+    (dealer.clamp(0.0, 1.0), player.clamp(0.0, 1.0))
+}
+
+/// Several grids of `tiles` × `tiles` tiles over dealer card and player sum. Each tiling
+/// is shifted by a different fraction of a tile, so that together they tell apart
+/// states that share a tile in one of them. Every tiling has one active feature.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TileCoding {
+    pub tilings: usize,
+    pub tiles: usize,
+}
+
+impl TileCoding {
+    /// Tiles per tiling, with one extra row and column for the shifted tiles.
+    fn tiles_per_tiling(&self) -> usize {
+        (self.tiles + 1) * (self.tiles + 1)
+    }
+}
+
+impl StateFeatureExtractor<Easy21Env> for TileCoding {
+    fn state_dimension(&self) -> usize {
+        self.tilings * self.tiles_per_tiling()
+    }
+
+    fn for_each_state<F: FnMut(usize, f64)>(&self, state: &State, mut f: F) {
+        let (dealer, player) = coordinates(state);
+        let tiles = self.tiles as f64;
+        for tiling in 0..self.tilings {
+            // Asymmetric shifts, so that the tilings don't all line up on the diagonal.
+            let shift = |k: usize| ((tiling * k) % self.tilings) as f64 / self.tilings as f64;
+            let i = (dealer * tiles + shift(1)) as usize;
+            let j = (player * tiles + shift(3)) as usize;
+            let tile = i * (self.tiles + 1) + j;
+            f(tiling * self.tiles_per_tiling() + tile, 1.0);
+        }
+    }
+}
+
+impl FeatureExtractor<Easy21Env> for TileCoding {
+    fn dimension(&self) -> usize {
+        Easy21Env::ACTIONS.len() * self.state_dimension()
+    }
+
+    fn for_each<F: FnMut(usize, f64)>(&self, state: &State, action: &Action, f: F) {
+        for_each_in_block::<Easy21Env, _, _>(self, state, action, f);
+    }
+}
+
+/// Features of [`RadialBasis`] below this count as inactive.
+const NEGLIGIBLE: f64 = 1e-6;
+
+/// Gaussian bumps of standard deviation `width` around a grid of `centres` × `centres`
+/// points, in the coordinates where the states of the default rules span the unit
+/// square. Only the bumps of nearby centres are active.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RadialBasis {
+    pub centres: usize,
+    pub width: f64,
+}
+
+impl StateFeatureExtractor<Easy21Env> for RadialBasis {
+    fn state_dimension(&self) -> usize {
+        self.centres * self.centres
+    }
+
+    fn for_each_state<F: FnMut(usize, f64)>(&self, state: &State, mut f: F) {
+        let (dealer, player) = coordinates(state);
+        let spacing = 1.0 / (self.centres.max(2) - 1) as f64;
+        for i in 0..self.centres {
+            for j in 0..self.centres {
+                let d = dealer - i as f64 * spacing;
+                let p = player - j as f64 * spacing;
+                let distance = d * d + p * p;
+                let x = (-distance / (2.0 * self.width * self.width)).exp();
+                if x > NEGLIGIBLE {
+                    f(i * self.centres + j, x);
+                }
+            }
+        }
+    }
+}
+
+impl FeatureExtractor<Easy21Env> for RadialBasis {
+    fn dimension(&self) -> usize {
+        Easy21Env::ACTIONS.len() * self.state_dimension()
+    }
+
+    fn for_each<F: FnMut(usize, f64)>(&self, state: &State, action: &Action, f: F) {
+        for_each_in_block::<Easy21Env, _, _>(self, state, action, f);
+    }
+}
+
+/// One of the extractors above, as chosen in the hyperparameters.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum Features {
+    #[default]
+    Cuboid,
+    OneHot,
+    TileCoding(TileCoding),
+    RadialBasis(RadialBasis),
+}
+
+impl FeatureExtractor<Easy21Env> for Features {
+    fn dimension(&self) -> usize {
+        match self {
+            Features::Cuboid => Cuboid.dimension(),
+            Features::OneHot => FeatureExtractor::<Easy21Env>::dimension(&OneHot),
+            Features::TileCoding(tile_coding) => tile_coding.dimension(),
+            Features::RadialBasis(radial_basis) => radial_basis.dimension(),
+        }
+    }
+
+    fn for_each<F: FnMut(usize, f64)>(&self, state: &State, action: &Action, f: F) {
+        match self {
+            Features::Cuboid => Cuboid.for_each(state, action, f),
+            Features::OneHot => FeatureExtractor::<Easy21Env>::for_each(&OneHot, state, action, f),
+            Features::TileCoding(tile_coding) => tile_coding.for_each(state, action, f),
+            Features::RadialBasis(radial_basis) => radial_basis.for_each(state, action, f),
+        }
+    }
+}
+
+impl StateFeatureExtractor<Easy21Env> for Features {
+    fn state_dimension(&self) -> usize {
+        match self {
+            Features::Cuboid => Cuboid.state_dimension(),
+            Features::OneHot => StateFeatureExtractor::<Easy21Env>::state_dimension(&OneHot),
+            Features::TileCoding(tile_coding) => tile_coding.state_dimension(),
+            Features::RadialBasis(radial_basis) => radial_basis.state_dimension(),
+        }
+    }
+
+    fn for_each_state<F: FnMut(usize, f64)>(&self, state: &State, f: F) {
+        match self {
+            Features::Cuboid => Cuboid.for_each_state(state, f),
+            Features::OneHot => {
+                StateFeatureExtractor::<Easy21Env>::for_each_state(&OneHot, state, f)
+            }
+            Features::TileCoding(tile_coding) => tile_coding.for_each_state(state, f),
+            Features::RadialBasis(radial_basis) => radial_basis.for_each_state(state, f),
+        }
+    }
+}
+
+impl Features {
+    /// Shows the extractor and its settings as rows of a two-column grid, and returns
+    /// whether anything changed.
+    pub fn ui(&mut self, ui: &mut egui::Ui) -> bool {
+        let mut changed = false;
+        ui.label("Features:")
+            .on_hover_text("Takes effect on the next restart.");
+        ui.horizontal(|ui| {
+            let kinds = [
+                ("Cuboids", Features::Cuboid),
+                ("One-hot", Features::OneHot),
+                (
+                    "Tile coding",
+                    Features::TileCoding(TileCoding {
+                        tilings: 8,
+                        tiles: 4,
+                    }),
+                ),
+                (
+                    "RBF",
+                    Features::RadialBasis(RadialBasis {
+                        centres: 5,
+                        width: 0.2,
+                    }),
+                ),
+            ];
+            for (label, features) in kinds {
+                let selected = std::mem::discriminant(self) == std::mem::discriminant(&features);
+                if ui.selectable_label(selected, label).clicked() && !selected {
+                    *self = features;
+                    changed = true;
+                }
+            }
+        });
+        ui.end_row();
+
+        match self {
+            Features::Cuboid | Features::OneHot => {}
+            Features::TileCoding(TileCoding { tilings, tiles }) => {
+                ui.label("Tilings:");
+                changed |= ui.add(egui::Slider::new(tilings, 1..=32)).changed();
+                ui.end_row();
+
+                ui.label("Tiles per dimension:");
+                changed |= ui.add(egui::Slider::new(tiles, 1..=20)).changed();
+                ui.end_row();
+            }
+            Features::RadialBasis(RadialBasis { centres, width }) => {
+                ui.label("Centres per dimension:");
+                changed |= ui.add(egui::Slider::new(centres, 2..=20)).changed();
+                ui.end_row();
+
+                ui.label("Width:");
+                changed |= ui
+                    .add(egui::Slider::new(width, 0.01..=1.0).logarithmic(true))
+                    .changed();
+                ui.end_row();
+            }
+        }
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tile_coding() {
+        let tile_coding = TileCoding {
+            tilings: 4,
+            tiles: 3,
+        };
+        let state = State {
+            dealer: 5,
+            player: 12,
+        };
+        let active = |state: &State, action: &Action| -> Vec<usize> {
+            let x = tile_coding.extract(state, action);
+            x.entries.iter().map(|(i, _)| *i).collect()
+        };
+        let hit = active(&state, &Action::Hit);
+        let stick = active(&state, &Action::Stick);
+        assert_eq!(hit.len(), 4);
+        assert!(hit.iter().all(|i| *i < tile_coding.dimension()));
+        assert!(hit.iter().all(|i| !stick.contains(i)));
+
+        // Neighbouring states share some tiles but not all.
+        let next = active(
+            &State {
+                player: 14,
+                ..state
+            },
+            &Action::Hit,
+        );
+        let shared = hit.iter().filter(|i| next.contains(i)).count();
+        assert!(0 < shared && shared < 4, "{}", shared);
+    }
+}
@@ -0,0 +1,861 @@
+//! Saving and loading learners.
+//!
+//! A [`Snapshot`] holds everything needed to continue training: the rules, the seed, the
+//! hyperparameters and the learned values. It can be written in two formats with the
+//! same content:
+//!
+//! - JSON, for reading and editing by hand. The top level is an object
+//!   `{"version": 13, "rules": {...}, "seed": 42, "learner": {...}}`. `rules` and the
+//!   `hyperparameters` inside `learner` have one key per field of [`Easy21Rules`] and
+//!   [`Hyperparameters`]. `learner` has a single key naming the algorithm, e.g.
+//!   `{"TDLambdaControl": {"v": [...], "q": [...], "episodes": 1000, "hyperparameters":
+//!   {...}}}`.
+//! - A compact binary form written with [`bincode`]: the same fields in the same order,
+//!   little-endian and without field names. Its first four bytes are the version.
+//!
+//! Tables are stored as flat arrays in the order of [`Tabular::state_index`], one entry
+//! per state for `V` and one block of states per action, in the order of
+//! [`Environment::ACTIONS`], for `Q`. Entries of counted tables are pairs `[value,
+//! visits]`, or `[value, weight]` for off-policy Monte Carlo. Linear learners store their
+//! weight vector as `weights`, with one weight per feature of the `features`
+//! hyperparameter, or per state feature for prediction. Gradient-TD learners also store
+//! their `secondary_weights`, and policy-gradient learners the `policy_weights` of their
+//! action preferences next to the `weights` of their state values. The least-squares
+//! learners always use the cuboids, and also store their batch: LSTD(λ) the `system`
+//! `{"a": [[...]], "b": [...]}` of its episodes, and LSPI the `transitions` it keeps.
+//! Dyna-Q stores its `model`: the `entries` of the outcomes seen after each
+//! state-action pair, in the layout of `Q`, with their counts and the real step at which
+//! the pair was `last_tried`, and the `tried` pairs in the order they were first tried.
+//! Its real `steps` so far go with it, as the clock of the bonus of Dyna-Q+. Eligibility
+//! traces are not stored, since they are reset at the start of every episode.
+//!
+//! [`Tabular::state_index`]: crate::env::Tabular::state_index
+//! [`Environment::ACTIONS`]: crate::env::Environment::ACTIONS
+
+use std::{collections::VecDeque, fmt};
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    batch::{BatchMethod, BatchState, System, Transition},
+    dyna::{DynaMethod, DynaState, Model},
+    example_policy,
+    features::{FeatureExtractor, Features, StateFeatureExtractor},
+    policy_gradient::{PolicyGradientMethod, PolicyGradientState},
+    Algorithm, ApproxPredictionState, ApproxState, DoubleQState, Easy21Env, Easy21Rules,
+    Easy21State, Hyperparameters, LinearControlMethod, LinearPredictionMethod, MCControlState,
+    MCState, OffPolicyMCState, Sampling, TDControlMethod, TDControlState, TDState, Vector, Q, V,
+};
+
+/// Version of the format written by this module.
+pub const VERSION: u32 = 13;
+
+/// Key of the snapshot in eframe's persistence storage.
+pub const STORAGE_KEY: &str = "easy21";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Json,
+    Binary,
+}
+
+impl Format {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Format::Json => "json",
+            Format::Binary => "bin",
+        }
+    }
+
+    pub fn encode(&self, snapshot: &Snapshot) -> Vec<u8> {
+        match self {
+            Format::Json => serde_json::to_vec(snapshot).expect("snapshots serialize to JSON"),
+            Format::Binary => bincode::serialize(snapshot).expect("snapshots serialize to bincode"),
+        }
+    }
+
+    pub fn decode(&self, bytes: &[u8]) -> Result<Snapshot, Error> {
+        let snapshot: Snapshot = match self {
+            Format::Json => serde_json::from_slice(bytes).map_err(Error::Json)?,
+            Format::Binary => bincode::deserialize(bytes).map_err(Error::Binary)?,
+        };
+        snapshot.check()
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Json(serde_json::Error),
+    Binary(bincode::Error),
+    /// The snapshot was written by another version of the format.
+    Version(u32),
+    /// The rules have states outside of the tables.
+    Rules(Easy21Rules),
+    /// A linear learner has a different number of weights than its features.
+    Weights(usize, Features),
+    /// The model of Dyna-Q doesn't fit the tables.
+    Model,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Json(e) => write!(f, "invalid JSON snapshot: {}", e),
+            Error::Binary(e) => write!(f, "invalid binary snapshot: {}", e),
+            Error::Version(v) => write!(f, "unsupported snapshot version {}", v),
+            Error::Rules(rules) => write!(f, "unsupported rules {:?}", rules),
+            Error::Weights(n, features) => write!(f, "{} weights don't fit {:?}", n, features),
+            Error::Model => write!(f, "the model doesn't fit the tables"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub version: u32,
+    pub rules: Easy21Rules,
+    pub seed: u64,
+    pub learner: Learner,
+}
+
+impl Snapshot {
+    pub fn new(rules: Easy21Rules, seed: u64, learner: Learner) -> Self {
+        Self {
+            version: VERSION,
+            rules,
+            seed,
+            learner,
+        }
+    }
+
+    fn check(self) -> Result<Self, Error> {
+        if self.version != VERSION {
+            return Err(Error::Version(self.version));
+        }
+        if !self.rules.is_supported() {
+            return Err(Error::Rules(self.rules));
+        }
+        let features = match &self.learner {
+            // The batch methods always use the cuboids.
+            Learner::LSTDLambdaPrediction { .. } | Learner::LSPIControl { .. } => Features::Cuboid,
+            learner => learner.hyperparameters().features,
+        };
+        let (weights, dimension) = match &self.learner {
+            Learner::ApproxTDLambdaControl { weights, .. }
+            | Learner::TrueOnlineSarsaLambda { weights, .. } => (weights, features.dimension()),
+            Learner::GradientMonteCarloPrediction { weights, .. }
+            | Learner::ApproxTDLambdaPrediction { weights, .. }
+            | Learner::OffPolicyTDLambdaPrediction { weights, .. } => {
+                (weights, features.state_dimension())
+            }
+            Learner::GTD2Prediction {
+                weights,
+                secondary_weights,
+                ..
+            }
+            | Learner::TDCPrediction {
+                weights,
+                secondary_weights,
+                ..
+            } => {
+                if secondary_weights.w.len() != weights.w.len() {
+                    return Err(Error::Weights(secondary_weights.w.len(), features));
+                }
+                (weights, features.state_dimension())
+            }
+            Learner::LSTDLambdaPrediction {
+                weights, system, ..
+            } => {
+                let dimension = features.state_dimension();
+                let square = system.a.iter().all(|row| row.len() == dimension);
+                if system.a.len() != dimension || !square || system.b.len() != dimension {
+                    return Err(Error::Weights(system.b.len(), features));
+                }
+                (weights, dimension)
+            }
+            Learner::LSPIControl { weights, .. } => (weights, features.dimension()),
+            Learner::DynaQ { model, .. } | Learner::DynaQPlus { model, .. } => {
+                return if model.fits() {
+                    Ok(self)
+                } else {
+                    Err(Error::Model)
+                };
+            }
+            Learner::Reinforce {
+                policy_weights,
+                weights,
+                ..
+            }
+            | Learner::OneStepActorCritic {
+                policy_weights,
+                weights,
+                ..
+            }
+            | Learner::ActorCriticLambda {
+                policy_weights,
+                weights,
+                ..
+            } => {
+                if policy_weights.w.len() != features.dimension() {
+                    return Err(Error::Weights(policy_weights.w.len(), features));
+                }
+                (weights, features.state_dimension())
+            }
+            _ => return Ok(self),
+        };
+        if weights.w.len() == dimension {
+            Ok(self)
+        } else {
+            Err(Error::Weights(weights.w.len(), features))
+        }
+    }
+}
+
+/// The learned values of each algorithm. The prediction algorithms always evaluate
+/// [`example_policy`].
+#[derive(Clone, Serialize, Deserialize)]
+pub enum Learner {
+    MonteCarloPrediction {
+        v: V<(f64, i32)>,
+        episodes: i32,
+        hyperparameters: Hyperparameters,
+    },
+    MonteCarloControl {
+        v: V<(f64, i32)>,
+        q: Q<(f64, i32)>,
+        episodes: i32,
+        hyperparameters: Hyperparameters,
+    },
+    TDLambdaPrediction {
+        v: V<(f64, i32)>,
+        episodes: i32,
+        hyperparameters: Hyperparameters,
+    },
+    TDLambdaControl {
+        v: V<(f64, i32)>,
+        q: Q<(f64, i32)>,
+        episodes: i32,
+        hyperparameters: Hyperparameters,
+    },
+    ApproxTDLambdaControl {
+        weights: Vector,
+        episodes: i32,
+        hyperparameters: Hyperparameters,
+    },
+    OffPolicyMCPrediction {
+        q: Q<(f64, f64)>,
+        sampling: Sampling,
+        episodes: i32,
+        hyperparameters: Hyperparameters,
+    },
+    OffPolicyMCControl {
+        q: Q<(f64, f64)>,
+        sampling: Sampling,
+        episodes: i32,
+        hyperparameters: Hyperparameters,
+    },
+    QLearning {
+        v: V<(f64, i32)>,
+        q: Q<(f64, i32)>,
+        episodes: i32,
+        hyperparameters: Hyperparameters,
+    },
+    WatkinsQLambda {
+        v: V<(f64, i32)>,
+        q: Q<(f64, i32)>,
+        episodes: i32,
+        hyperparameters: Hyperparameters,
+    },
+    ExpectedSarsa {
+        v: V<(f64, i32)>,
+        q: Q<(f64, i32)>,
+        episodes: i32,
+        hyperparameters: Hyperparameters,
+    },
+    DoubleQLearning {
+        v: V<(f64, i32)>,
+        q1: Q<(f64, i32)>,
+        q2: Q<(f64, i32)>,
+        episodes: i32,
+        hyperparameters: Hyperparameters,
+    },
+    NStepSarsa {
+        v: V<(f64, i32)>,
+        q: Q<(f64, i32)>,
+        episodes: i32,
+        hyperparameters: Hyperparameters,
+    },
+    NStepTreeBackup {
+        v: V<(f64, i32)>,
+        q: Q<(f64, i32)>,
+        episodes: i32,
+        hyperparameters: Hyperparameters,
+    },
+    TrueOnlineSarsaLambda {
+        weights: Vector,
+        episodes: i32,
+        hyperparameters: Hyperparameters,
+    },
+    GradientMonteCarloPrediction {
+        weights: Vector,
+        episodes: i32,
+        hyperparameters: Hyperparameters,
+    },
+    ApproxTDLambdaPrediction {
+        weights: Vector,
+        episodes: i32,
+        hyperparameters: Hyperparameters,
+    },
+    OffPolicyTDLambdaPrediction {
+        weights: Vector,
+        episodes: i32,
+        hyperparameters: Hyperparameters,
+    },
+    GTD2Prediction {
+        weights: Vector,
+        secondary_weights: Vector,
+        episodes: i32,
+        hyperparameters: Hyperparameters,
+    },
+    TDCPrediction {
+        weights: Vector,
+        secondary_weights: Vector,
+        episodes: i32,
+        hyperparameters: Hyperparameters,
+    },
+    LSTDLambdaPrediction {
+        weights: Vector,
+        system: System,
+        episodes: i32,
+        hyperparameters: Hyperparameters,
+    },
+    LSPIControl {
+        weights: Vector,
+        transitions: VecDeque<Transition<Easy21Env>>,
+        episodes: i32,
+        hyperparameters: Hyperparameters,
+    },
+    Reinforce {
+        policy_weights: Vector,
+        weights: Vector,
+        episodes: i32,
+        hyperparameters: Hyperparameters,
+    },
+    OneStepActorCritic {
+        policy_weights: Vector,
+        weights: Vector,
+        episodes: i32,
+        hyperparameters: Hyperparameters,
+    },
+    ActorCriticLambda {
+        policy_weights: Vector,
+        weights: Vector,
+        episodes: i32,
+        hyperparameters: Hyperparameters,
+    },
+    DynaQ {
+        q: Q<(f64, i32)>,
+        model: Model<Easy21Env>,
+        steps: i64,
+        episodes: i32,
+        hyperparameters: Hyperparameters,
+    },
+    DynaQPlus {
+        q: Q<(f64, i32)>,
+        model: Model<Easy21Env>,
+        steps: i64,
+        episodes: i32,
+        hyperparameters: Hyperparameters,
+    },
+}
+
+impl Learner {
+    pub(super) fn algorithm(&self) -> Algorithm {
+        match self {
+            Learner::MonteCarloPrediction { .. } => Algorithm::MonteCarloPrediction,
+            Learner::MonteCarloControl { .. } => Algorithm::MonteCarloControl,
+            Learner::TDLambdaPrediction { .. } => Algorithm::TDLambdaPrediction,
+            Learner::TDLambdaControl { .. } => Algorithm::TDLambdaControl,
+            Learner::QLearning { .. } => Algorithm::QLearning,
+            Learner::WatkinsQLambda { .. } => Algorithm::WatkinsQLambda,
+            Learner::ExpectedSarsa { .. } => Algorithm::ExpectedSarsa,
+            Learner::DoubleQLearning { .. } => Algorithm::DoubleQLearning,
+            Learner::NStepSarsa { .. } => Algorithm::NStepSarsa,
+            Learner::NStepTreeBackup { .. } => Algorithm::NStepTreeBackup,
+            Learner::TrueOnlineSarsaLambda { .. } => Algorithm::TrueOnlineSarsaLambda,
+            Learner::ApproxTDLambdaControl { .. } => Algorithm::ApproxTDLambdaControl,
+            Learner::OffPolicyMCPrediction { sampling, .. } => {
+                Algorithm::OffPolicyMCPrediction(*sampling)
+            }
+            Learner::OffPolicyMCControl { sampling, .. } => {
+                Algorithm::OffPolicyMCControl(*sampling)
+            }
+            Learner::GradientMonteCarloPrediction { .. } => Algorithm::GradientMonteCarloPrediction,
+            Learner::ApproxTDLambdaPrediction { .. } => Algorithm::ApproxTDLambdaPrediction,
+            Learner::OffPolicyTDLambdaPrediction { .. } => Algorithm::OffPolicyTDLambdaPrediction,
+            Learner::GTD2Prediction { .. } => Algorithm::GTD2Prediction,
+            Learner::TDCPrediction { .. } => Algorithm::TDCPrediction,
+            Learner::LSTDLambdaPrediction { .. } => Algorithm::LSTDLambdaPrediction,
+            Learner::LSPIControl { .. } => Algorithm::LSPIControl,
+            Learner::Reinforce { .. } => Algorithm::Reinforce,
+            Learner::OneStepActorCritic { .. } => Algorithm::OneStepActorCritic,
+            Learner::ActorCriticLambda { .. } => Algorithm::ActorCriticLambda,
+            Learner::DynaQ { .. } => Algorithm::DynaQ,
+            Learner::DynaQPlus { .. } => Algorithm::DynaQPlus,
+        }
+    }
+
+    /// The hyperparameters the learner was trained with.
+    pub fn hyperparameters(&self) -> Hyperparameters {
+        match self {
+            Learner::MonteCarloPrediction {
+                hyperparameters, ..
+            }
+            | Learner::MonteCarloControl {
+                hyperparameters, ..
+            }
+            | Learner::TDLambdaPrediction {
+                hyperparameters, ..
+            }
+            | Learner::TDLambdaControl {
+                hyperparameters, ..
+            }
+            | Learner::QLearning {
+                hyperparameters, ..
+            }
+            | Learner::WatkinsQLambda {
+                hyperparameters, ..
+            }
+            | Learner::ExpectedSarsa {
+                hyperparameters, ..
+            }
+            | Learner::DoubleQLearning {
+                hyperparameters, ..
+            }
+            | Learner::NStepSarsa {
+                hyperparameters, ..
+            }
+            | Learner::NStepTreeBackup {
+                hyperparameters, ..
+            }
+            | Learner::TrueOnlineSarsaLambda {
+                hyperparameters, ..
+            }
+            | Learner::ApproxTDLambdaControl {
+                hyperparameters, ..
+            }
+            | Learner::OffPolicyMCPrediction {
+                hyperparameters, ..
+            }
+            | Learner::OffPolicyMCControl {
+                hyperparameters, ..
+            }
+            | Learner::GradientMonteCarloPrediction {
+                hyperparameters, ..
+            }
+            | Learner::ApproxTDLambdaPrediction {
+                hyperparameters, ..
+            }
+            | Learner::OffPolicyTDLambdaPrediction {
+                hyperparameters, ..
+            }
+            | Learner::GTD2Prediction {
+                hyperparameters, ..
+            }
+            | Learner::TDCPrediction {
+                hyperparameters, ..
+            }
+            | Learner::LSTDLambdaPrediction {
+                hyperparameters, ..
+            }
+            | Learner::LSPIControl {
+                hyperparameters, ..
+            }
+            | Learner::Reinforce {
+                hyperparameters, ..
+            }
+            | Learner::OneStepActorCritic {
+                hyperparameters, ..
+            }
+            | Learner::ActorCriticLambda {
+                hyperparameters, ..
+            }
+            | Learner::DynaQ {
+                hyperparameters, ..
+            }
+            | Learner::DynaQPlus {
+                hyperparameters, ..
+            } => *hyperparameters,
+        }
+    }
+
+    pub(super) fn into_state(self) -> Box<dyn Easy21State> {
+        match self {
+            Learner::MonteCarloPrediction {
+                v,
+                episodes,
+                hyperparameters,
+            } => Box::new(MCState {
+                v,
+                episodes,
+                ..MCState::init(hyperparameters, example_policy)
+            }),
+            Learner::MonteCarloControl {
+                v,
+                q,
+                episodes,
+                hyperparameters,
+            } => Box::new(MCControlState {
+                v,
+                q,
+                episodes,
+                hyperparameters,
+            }),
+            Learner::TDLambdaPrediction {
+                v,
+                episodes,
+                hyperparameters,
+            } => Box::new(TDState {
+                v,
+                episodes,
+                ..TDState::init(hyperparameters, example_policy)
+            }),
+            Learner::TDLambdaControl {
+                v,
+                q,
+                episodes,
+                hyperparameters,
+            } => Box::new(TDControlState {
+                v,
+                q,
+                episodes,
+                ..TDControlState::init(hyperparameters)
+            }),
+            Learner::ApproxTDLambdaControl {
+                weights,
+                episodes,
+                hyperparameters,
+            } => Box::new(ApproxState {
+                q: weights,
+                episodes,
+                ..ApproxState::init(hyperparameters, hyperparameters.features)
+            }),
+            Learner::OffPolicyMCPrediction {
+                q,
+                sampling,
+                episodes,
+                hyperparameters,
+            } => Box::new(OffPolicyMCState {
+                q,
+                episodes,
+                ..OffPolicyMCState::init(hyperparameters, sampling, Some(example_policy))
+            }),
+            Learner::OffPolicyMCControl {
+                q,
+                sampling,
+                episodes,
+                hyperparameters,
+            } => Box::new(OffPolicyMCState {
+                q,
+                episodes,
+                ..OffPolicyMCState::init(hyperparameters, sampling, None)
+            }),
+            Learner::QLearning {
+                v,
+                q,
+                episodes,
+                hyperparameters,
+            } => Box::new(TDControlState {
+                v,
+                q,
+                episodes,
+                method: TDControlMethod::QLearning,
+                ..TDControlState::init(hyperparameters)
+            }),
+            Learner::WatkinsQLambda {
+                v,
+                q,
+                episodes,
+                hyperparameters,
+            } => Box::new(TDControlState {
+                v,
+                q,
+                episodes,
+                method: TDControlMethod::WatkinsQLambda,
+                ..TDControlState::init(hyperparameters)
+            }),
+            Learner::ExpectedSarsa {
+                v,
+                q,
+                episodes,
+                hyperparameters,
+            } => Box::new(TDControlState {
+                v,
+                q,
+                episodes,
+                method: TDControlMethod::ExpectedSarsa,
+                ..TDControlState::init(hyperparameters)
+            }),
+            Learner::DoubleQLearning {
+                v,
+                q1,
+                q2,
+                episodes,
+                hyperparameters,
+            } => Box::new(DoubleQState {
+                v,
+                q1,
+                q2,
+                episodes,
+                hyperparameters,
+            }),
+            Learner::NStepSarsa {
+                v,
+                q,
+                episodes,
+                hyperparameters,
+            } => Box::new(TDControlState {
+                v,
+                q,
+                episodes,
+                method: TDControlMethod::NStepSarsa,
+                ..TDControlState::init(hyperparameters)
+            }),
+            Learner::NStepTreeBackup {
+                v,
+                q,
+                episodes,
+                hyperparameters,
+            } => Box::new(TDControlState {
+                v,
+                q,
+                episodes,
+                method: TDControlMethod::NStepTreeBackup,
+                ..TDControlState::init(hyperparameters)
+            }),
+            Learner::TrueOnlineSarsaLambda {
+                weights,
+                episodes,
+                hyperparameters,
+            } => Box::new(ApproxState {
+                q: weights,
+                episodes,
+                method: LinearControlMethod::TrueOnlineSarsa,
+                ..ApproxState::init(hyperparameters, hyperparameters.features)
+            }),
+            Learner::GradientMonteCarloPrediction {
+                weights,
+                episodes,
+                hyperparameters,
+            } => Box::new(ApproxPredictionState {
+                v: weights,
+                episodes,
+                method: LinearPredictionMethod::GradientMonteCarlo,
+                ..ApproxPredictionState::init(
+                    hyperparameters,
+                    hyperparameters.features,
+                    example_policy,
+                )
+            }),
+            Learner::ApproxTDLambdaPrediction {
+                weights,
+                episodes,
+                hyperparameters,
+            } => Box::new(ApproxPredictionState {
+                v: weights,
+                episodes,
+                ..ApproxPredictionState::init(
+                    hyperparameters,
+                    hyperparameters.features,
+                    example_policy,
+                )
+            }),
+            Learner::OffPolicyTDLambdaPrediction {
+                weights,
+                episodes,
+                hyperparameters,
+            } => Box::new(ApproxPredictionState {
+                v: weights,
+                episodes,
+                method: LinearPredictionMethod::OffPolicyTDLambda,
+                ..ApproxPredictionState::init(
+                    hyperparameters,
+                    hyperparameters.features,
+                    example_policy,
+                )
+            }),
+            Learner::GTD2Prediction {
+                weights,
+                secondary_weights,
+                episodes,
+                hyperparameters,
+            } => Box::new(ApproxPredictionState {
+                v: weights,
+                secondary: secondary_weights,
+                episodes,
+                method: LinearPredictionMethod::GTD2,
+                ..ApproxPredictionState::init(
+                    hyperparameters,
+                    hyperparameters.features,
+                    example_policy,
+                )
+            }),
+            Learner::TDCPrediction {
+                weights,
+                secondary_weights,
+                episodes,
+                hyperparameters,
+            } => Box::new(ApproxPredictionState {
+                v: weights,
+                secondary: secondary_weights,
+                episodes,
+                method: LinearPredictionMethod::TDC,
+                ..ApproxPredictionState::init(
+                    hyperparameters,
+                    hyperparameters.features,
+                    example_policy,
+                )
+            }),
+            Learner::LSTDLambdaPrediction {
+                weights,
+                system,
+                episodes,
+                hyperparameters,
+            } => Box::new(BatchState {
+                weights,
+                system,
+                episodes,
+                ..BatchState::init(hyperparameters, BatchMethod::LSTDLambdaPrediction)
+            }),
+            Learner::LSPIControl {
+                weights,
+                transitions,
+                episodes,
+                hyperparameters,
+            } => Box::new(BatchState {
+                weights,
+                transitions,
+                episodes,
+                ..BatchState::init(hyperparameters, BatchMethod::LSPIControl)
+            }),
+            Learner::Reinforce {
+                policy_weights,
+                weights,
+                episodes,
+                hyperparameters,
+            } => Box::new(PolicyGradientState {
+                theta: policy_weights,
+                v: weights,
+                episodes,
+                ..PolicyGradientState::init(hyperparameters, hyperparameters.features)
+            }),
+            Learner::OneStepActorCritic {
+                policy_weights,
+                weights,
+                episodes,
+                hyperparameters,
+            } => Box::new(PolicyGradientState {
+                theta: policy_weights,
+                v: weights,
+                episodes,
+                method: PolicyGradientMethod::OneStepActorCritic,
+                ..PolicyGradientState::init(hyperparameters, hyperparameters.features)
+            }),
+            Learner::ActorCriticLambda {
+                policy_weights,
+                weights,
+                episodes,
+                hyperparameters,
+            } => Box::new(PolicyGradientState {
+                theta: policy_weights,
+                v: weights,
+                episodes,
+                method: PolicyGradientMethod::ActorCriticLambda,
+                ..PolicyGradientState::init(hyperparameters, hyperparameters.features)
+            }),
+            Learner::DynaQ {
+                q,
+                model,
+                steps,
+                episodes,
+                hyperparameters,
+            } => Box::new(DynaState {
+                q,
+                model,
+                steps,
+                episodes,
+                ..DynaState::init(hyperparameters)
+            }),
+            Learner::DynaQPlus {
+                q,
+                model,
+                steps,
+                episodes,
+                hyperparameters,
+            } => Box::new(DynaState {
+                q,
+                model,
+                steps,
+                episodes,
+                method: DynaMethod::DynaQPlus,
+                ..DynaState::init(hyperparameters)
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+    use crate::easy_21::{exploration::Strategy, td_lambda_control, Easy21Env};
+
+    #[test]
+    fn test_round_trip() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut td_state = TDControlState::init(Hyperparameters::default());
+        for _ in 0..100 {
+            let env = Easy21Env::default();
+            td_lambda_control(&env, &mut rng, &Strategy::default(), &mut td_state);
+        }
+        let snapshot = Snapshot::new(Easy21Rules::default(), 7, td_state.save());
+        for format in [Format::Json, Format::Binary] {
+            let loaded = format.decode(&format.encode(&snapshot)).unwrap();
+            assert_eq!(loaded.seed, 7);
+            match loaded.learner {
+                Learner::TDLambdaControl { q, episodes, .. } => {
+                    assert_eq!(q.0, td_state.q.0);
+                    assert_eq!(episodes, 100);
+                }
+                _ => panic!("wrong learner"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_unsupported_rules() {
+        let learner = TDControlState::init(Hyperparameters::default()).save();
+        let default = Easy21Rules::default();
+        for rules in [
+            Easy21Rules {
+                red_probability: 1.5,
+                ..default
+            },
+            Easy21Rules {
+                red_probability: f64::NAN,
+                ..default
+            },
+            Easy21Rules {
+                dealer_sticks_at: default.max_sum + 1,
+                ..default
+            },
+        ] {
+            let snapshot = Snapshot::new(rules, 0, learner.clone());
+            assert!(matches!(snapshot.check(), Err(Error::Rules(_))));
+        }
+        assert!(Snapshot::new(default, 0, learner).check().is_ok());
+    }
+}
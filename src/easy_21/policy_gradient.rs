@@ -0,0 +1,359 @@
+//! Policy-gradient learners: a stochastic policy with its own weights, improved along the
+//! gradient of its expected return.
+//!
+//! The policy is a softmax over action preferences, which are linear in the action
+//! features. Next to it the learners keep linear state values over the state features
+//! of the same extractor. REINFORCE may subtract them from the returns as a baseline,
+//! actor-critic methods learn them by TD(λ) as their critic, and they give the chart a V
+//! to draw either way.
+
+use std::{cell::Cell, collections::VecDeque, marker::PhantomData};
+
+use rand::{rngs::StdRng, Rng};
+
+use super::{
+    batch::LinearQ,
+    dp,
+    exploration::{Boltzmann, Exploration, Schedule},
+    features::{FeatureExtractor, Features, StateFeatureExtractor},
+    persistence, Action, Easy21Env, Easy21Rules, Easy21State, HasV, Hyperparameters, SparseVector,
+    State, Trace, Traces, Vector,
+};
+use crate::env::Environment;
+
+/// The softmax over preferences, as Boltzmann exploration at temperature one.
+const SOFTMAX: Boltzmann = Boltzmann(Schedule::Constant(1.0));
+
+/// Number of recent episodes that the win rate counts.
+const WIN_RATE_EPISODES: usize = 1000;
+
+/// The policy-gradient algorithms that share [`PolicyGradientState`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PolicyGradientMethod {
+    /// Monte Carlo policy gradient, with the state values as an optional baseline.
+    Reinforce,
+    /// Moves the policy and the state values by the one-step TD error after every step.
+    OneStepActorCritic,
+    /// Like the one-step method, but along eligibility traces of the gradients of both
+    /// the policy and the state values, which decay by λ.
+    ActorCriticLambda,
+}
+
+pub struct PolicyGradientState<E: Environment = Easy21Env, F = Features> {
+    /// Weights of the action preferences.
+    pub theta: Vector,
+    /// Weights of the state values.
+    pub v: Vector,
+    pub actor_traces: Traces,
+    pub critic_traces: Traces,
+    pub features: F,
+    pub episodes: i32,
+    /// Whether each of the last [`WIN_RATE_EPISODES`] episodes was won.
+    pub outcomes: VecDeque<bool>,
+    pub method: PolicyGradientMethod,
+    pub hyperparameters: Hyperparameters,
+    /// The last error against V^π, with the episode count and rules it was measured at,
+    /// so that the policy is evaluated at most once per episode.
+    pub error: Cell<Option<(i32, Easy21Rules, f64)>>,
+    pub environment: PhantomData<E>,
+}
+
+impl<E: Environment, F: FeatureExtractor<E> + StateFeatureExtractor<E>> PolicyGradientState<E, F> {
+    /// REINFORCE with a uniform initial policy and zero state values. Set `method` for
+    /// actor-critic.
+    pub fn init(hyperparameters: Hyperparameters, features: F) -> Self {
+        Self {
+            theta: Vector::init(features.dimension()),
+            v: Vector::init(features.state_dimension()),
+            actor_traces: Traces::default(),
+            critic_traces: Traces::default(),
+            features,
+            episodes: 0,
+            outcomes: VecDeque::new(),
+            method: PolicyGradientMethod::Reinforce,
+            hyperparameters,
+            error: Cell::new(None),
+            environment: PhantomData,
+        }
+    }
+
+    /// Probability of each action in `state`, in the order of `E::ACTIONS`.
+    pub fn probabilities(&self, state: &E::State) -> Vec<f64> {
+        SOFTMAX.probabilities(&self.preferences(), state)
+    }
+
+    /// Samples an action from the policy.
+    pub fn choose<R: Rng>(&self, rng: &mut R, state: &E::State) -> E::Action {
+        SOFTMAX.choose(rng, &self.preferences(), state)
+    }
+
+    fn preferences(&self) -> LinearQ<'_, F> {
+        LinearQ {
+            features: &self.features,
+            weights: &self.theta,
+        }
+    }
+
+    pub fn value(&self, state: &E::State) -> f64 {
+        self.features.state_value(state, &self.v)
+    }
+
+    /// The gradient of the log-probability of `action` in `state` with respect to the
+    /// preference weights: the features of the action minus their expectation under the
+    /// policy.
+    fn log_gradient(&self, state: &E::State, action: &E::Action) -> SparseVector {
+        let probabilities = self.probabilities(state);
+        let mut gradient = SparseVector::default();
+        for (a, p) in E::ACTIONS.iter().zip(probabilities) {
+            let scale = if a == action { 1.0 - p } else { -p };
+            let x = self.features.extract(state, a);
+            gradient
+                .entries
+                .extend(x.entries.iter().map(|(i, x)| (*i, scale * x)));
+        }
+        gradient
+    }
+
+    /// Counts a finished episode.
+    fn finish_episode(&mut self, episode_return: f64) {
+        self.outcomes.push_back(episode_return > 0.0);
+        if self.outcomes.len() > WIN_RATE_EPISODES {
+            self.outcomes.pop_front();
+        }
+        self.episodes += 1;
+    }
+}
+
+impl HasV for PolicyGradientState {
+    fn get_v(&self, state: &State) -> f64 {
+        self.value(state)
+    }
+}
+
+impl Easy21State for PolicyGradientState {
+    fn update(&mut self, env: &Easy21Env, rng: &mut StdRng) {
+        match self.method {
+            PolicyGradientMethod::Reinforce => reinforce(env, rng, self),
+            PolicyGradientMethod::OneStepActorCritic | PolicyGradientMethod::ActorCriticLambda => {
+                actor_critic(env, rng, self)
+            }
+        }
+    }
+    fn episodes(&self) -> i32 {
+        self.episodes
+    }
+    /// The more probable action.
+    fn policy(&self, state: &State) -> Action {
+        let probabilities = self.probabilities(state);
+        if probabilities[0] > probabilities[1] {
+            Easy21Env::ACTIONS[0]
+        } else {
+            Easy21Env::ACTIONS[1]
+        }
+    }
+    /// The error of the state values against V^π of the current policy, which the critic
+    /// estimates.
+    fn rms_error(&self, reference: &dp::Reference) -> f64 {
+        let rules = reference.optimal.rules;
+        match self.error.get() {
+            Some((episodes, r, error)) if episodes == self.episodes && r == rules => error,
+            _ => {
+                let solution = dp::evaluate_stochastic(&rules, |s| self.probabilities(s));
+                let error = solution.rms_error_v(self);
+                self.error.set(Some((self.episodes, rules, error)));
+                error
+            }
+        }
+    }
+    /// Over the last [`WIN_RATE_EPISODES`] episodes.
+    fn win_rate(&self) -> Option<f64> {
+        let wins = self.outcomes.iter().filter(|won| **won).count();
+        Some(wins as f64 / self.outcomes.len().max(1) as f64)
+    }
+    fn set_hyperparameters(&mut self, h: Hyperparameters) {
+        self.hyperparameters = h;
+    }
+    fn save(&self) -> persistence::Learner {
+        let hyperparameters = Hyperparameters {
+            features: self.features,
+            ..self.hyperparameters
+        };
+        let policy_weights = self.theta.clone();
+        let weights = self.v.clone();
+        let episodes = self.episodes;
+        match self.method {
+            PolicyGradientMethod::Reinforce => persistence::Learner::Reinforce {
+                policy_weights,
+                weights,
+                episodes,
+                hyperparameters,
+            },
+            PolicyGradientMethod::OneStepActorCritic => persistence::Learner::OneStepActorCritic {
+                policy_weights,
+                weights,
+                episodes,
+                hyperparameters,
+            },
+            PolicyGradientMethod::ActorCriticLambda => persistence::Learner::ActorCriticLambda {
+                policy_weights,
+                weights,
+                episodes,
+                hyperparameters,
+            },
+        }
+    }
+}
+
+/// One episode of REINFORCE: after the episode, every step moves the policy towards its
+/// action in proportion to the return that followed, less the baseline if enabled. The
+/// state values always learn the returns, at the critic step size.
+pub fn reinforce<E, R, F>(env: &E, rng: &mut R, pg_state: &mut PolicyGradientState<E, F>)
+where
+    E: Environment,
+    R: Rng,
+    F: FeatureExtractor<E> + StateFeatureExtractor<E>,
+{
+    let h = pg_state.hyperparameters;
+    let alpha = h.step_size.at(pg_state.episodes);
+    let critic_alpha = h.critic_step_size.at(pg_state.episodes);
+
+    let mut steps = vec![];
+    let mut state = env.reset(rng);
+    loop {
+        let action = pg_state.choose(rng, &state);
+        let sample = env.step(rng, state, action);
+        steps.push((state, action, sample.reward));
+        if sample.terminal {
+            break;
+        }
+        state = sample.state;
+    }
+
+    let total: f64 = steps.iter().map(|(_, _, reward)| reward).sum();
+    let mut episode_return = total;
+    for (state, action, reward) in &steps {
+        let x = pg_state.features.extract_state(state);
+        let error = episode_return - x.dot(&pg_state.v);
+        let advantage = if h.baseline { error } else { episode_return };
+        pg_state.v.add_sparse(&x, critic_alpha * error);
+        let gradient = pg_state.log_gradient(state, action);
+        pg_state.theta.add_sparse(&gradient, alpha * advantage);
+        episode_return -= reward;
+    }
+    pg_state.finish_episode(total);
+}
+
+/// One episode of actor-critic: after every step, the TD error of the state values moves
+/// the policy along the actor traces, at the step size, and the state values along the
+/// critic traces, at the critic step size. The one-step method is the special case
+/// λ = 0.
+pub fn actor_critic<E, R, F>(env: &E, rng: &mut R, pg_state: &mut PolicyGradientState<E, F>)
+where
+    E: Environment,
+    R: Rng,
+    F: FeatureExtractor<E> + StateFeatureExtractor<E>,
+{
+    let h = pg_state.hyperparameters;
+    let alpha = h.step_size.at(pg_state.episodes);
+    let critic_alpha = h.critic_step_size.at(pg_state.episodes);
+    let lambda = match pg_state.method {
+        PolicyGradientMethod::ActorCriticLambda => h.lambda,
+        _ => 0.0,
+    };
+
+    pg_state.actor_traces.clear();
+    pg_state.critic_traces.clear();
+    let mut episode_return = 0.0;
+    let mut state = env.reset(rng);
+    loop {
+        let action = pg_state.choose(rng, &state);
+        let sample = env.step(rng, state, action);
+        episode_return += sample.reward;
+
+        let x = pg_state.features.extract_state(&state);
+        let next_value = if sample.terminal {
+            0.0
+        } else {
+            pg_state.value(&sample.state)
+        };
+        let error = sample.reward + next_value - x.dot(&pg_state.v);
+        let gradient = pg_state.log_gradient(&state, &action);
+        Trace::Accumulating.update_linear(&mut pg_state.critic_traces, &x, lambda, critic_alpha);
+        Trace::Accumulating.update_linear(&mut pg_state.actor_traces, &gradient, lambda, alpha);
+        pg_state
+            .v
+            .zip_traces(&pg_state.critic_traces, |w, e| w + critic_alpha * error * e);
+        pg_state
+            .theta
+            .zip_traces(&pg_state.actor_traces, |w, e| w + alpha * error * e);
+
+        if sample.terminal {
+            break;
+        }
+        state = sample.state;
+    }
+    pg_state.finish_episode(episode_return);
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+    use crate::easy_21::Algorithm;
+
+    #[test]
+    fn test_policy_gradient() {
+        let env = Easy21Env::default();
+        let reference = dp::Reference::new(&env.rules);
+        // The mean value of the most probable actions, against that of the optimal ones.
+        let mean_value = |solution: &dp::Solution| {
+            let states: Vec<_> = dp::states(&env.rules).collect();
+            states.iter().map(|s| solution.v.get(s)).sum::<f64>() / states.len() as f64
+        };
+        let optimal = mean_value(&reference.optimal);
+        for algorithm in [
+            Algorithm::Reinforce,
+            Algorithm::OneStepActorCritic,
+            Algorithm::ActorCriticLambda,
+        ] {
+            let mut rng = StdRng::seed_from_u64(0);
+            let mut pg_state = algorithm.initial_state(algorithm.hyperparameters());
+            for _ in 0..100_000 {
+                pg_state.update(&env, &mut rng);
+            }
+            let learned = mean_value(&dp::evaluate(&env.rules, |s| pg_state.policy(s)));
+            assert!(optimal - learned < 0.03, "{:?}: {}", algorithm, learned);
+            let error = pg_state.rms_error(&reference);
+            assert!(error < 0.25, "{:?}: {}", algorithm, error);
+            let win_rate = pg_state.win_rate().unwrap();
+            assert!(win_rate > 0.45, "{:?}: {}", algorithm, win_rate);
+        }
+    }
+
+    #[test]
+    fn test_actor_critic_traces() {
+        // From zero values only the last step of an episode has a TD error, so the
+        // one-step critic moves the value of that state alone, while the traces carry the
+        // error back to the earlier states.
+        let env = Easy21Env::default();
+        let h = Algorithm::ActorCriticLambda.hyperparameters();
+        let moved = |method, seed| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let mut pg_state = PolicyGradientState {
+                method,
+                ..PolicyGradientState::init(h, Features::OneHot)
+            };
+            actor_critic(&env, &mut rng, &mut pg_state);
+            pg_state.v.w.iter().filter(|w| **w != 0.0).count()
+        };
+        let mut credited = 0;
+        for seed in 0..100 {
+            assert!(moved(PolicyGradientMethod::OneStepActorCritic, seed) <= 1);
+            if moved(PolicyGradientMethod::ActorCriticLambda, seed) > 1 {
+                credited += 1;
+            }
+        }
+        assert!(credited > 10, "{}", credited);
+    }
+}
@@ -0,0 +1,409 @@
+//! Derivative-free policy search: instead of learning values, sample a population of
+//! deterministic policies from a search distribution, score each by the mean return of a
+//! batch of episodes, and move the distribution towards the better ones.
+//!
+//! Policies are either greedy with respect to linear action values, or tabular with one
+//! weight per state that hits when positive. The search distribution is a Gaussian over
+//! the weights with a standard deviation per weight. The cross-entropy method refits it
+//! to the elite, the best fraction of each generation. Evolution strategies keep its
+//! spread and move its mean along an estimate of the gradient of the expected score,
+//! from the scores of all candidates.
+
+use std::f64::consts::TAU;
+
+use plotters::prelude::*;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use super::{
+    batch::LinearQ,
+    episode,
+    features::{FeatureExtractor, Features},
+    greedy, Action, Easy21Env, State, Vector,
+};
+use crate::env::Tabular;
+
+/// Added to the standard deviations that the cross-entropy method fits, so that the
+/// search keeps exploring after the elite agree.
+const EXTRA_NOISE: f64 = 0.05;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Method {
+    CrossEntropy,
+    EvolutionStrategies,
+}
+
+/// The deterministic policies that the weights describe.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PolicyClass {
+    /// Greedy with respect to linear action values.
+    Linear(Features),
+    /// One weight per state: hit if it is positive, stick otherwise.
+    Tabular,
+}
+
+impl PolicyClass {
+    /// Number of weights.
+    pub fn dimension(&self) -> usize {
+        match self {
+            PolicyClass::Linear(features) => features.dimension(),
+            PolicyClass::Tabular => Easy21Env::STATES,
+        }
+    }
+
+    pub fn action(&self, weights: &Vector, state: &State) -> Action {
+        match self {
+            PolicyClass::Linear(features) => {
+                greedy::<Easy21Env, _>(&LinearQ { features, weights }, state)
+            }
+            PolicyClass::Tabular => {
+                if weights.w[Easy21Env::state_index(state)] > 0.0 {
+                    Action::Hit
+                } else {
+                    Action::Stick
+                }
+            }
+        }
+    }
+}
+
+/// A sample of the standard normal distribution, by the Box-Muller transform.
+fn standard_normal<R: Rng>(rng: &mut R) -> f64 {
+    // In (0, 1], so that the logarithm is finite.
+    let u = 1.0 - rng.gen::<f64>();
+    let v = rng.gen::<f64>();
+    (-2.0 * u.ln()).sqrt() * (TAU * v).cos()
+}
+
+/// Mean return of `rollouts` episodes with the policy of `weights`.
+pub fn score<R: Rng>(
+    env: &Easy21Env,
+    rng: &mut R,
+    class: &PolicyClass,
+    weights: &Vector,
+    rollouts: usize,
+) -> f64 {
+    let total: f64 = (0..rollouts)
+        .map(|_| episode(env, rng, |_, state| class.action(weights, state)).1)
+        .sum();
+    total / rollouts as f64
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Settings {
+    pub method: Method,
+    pub class: PolicyClass,
+    /// Candidates per generation.
+    pub population: usize,
+    /// Episodes per candidate.
+    pub rollouts: usize,
+    /// Fraction of the candidates that the cross-entropy method refits to. The mean
+    /// score of this fraction is the elite return of either method.
+    pub elite_fraction: f64,
+    /// Initial standard deviation of every weight. Evolution strategies keep it.
+    pub sigma: f64,
+    /// Step size of evolution strategies on the mean.
+    pub learning_rate: f64,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            method: Method::CrossEntropy,
+            class: PolicyClass::Linear(Features::Cuboid),
+            population: 50,
+            rollouts: 100,
+            elite_fraction: 0.2,
+            sigma: 1.0,
+            learning_rate: 1.0,
+        }
+    }
+}
+
+pub struct PolicySearch {
+    pub settings: Settings,
+    /// Mean of the search distribution.
+    pub mean: Vector,
+    /// Standard deviation of each weight in the search distribution.
+    pub std: Vec<f64>,
+    pub generation: usize,
+}
+
+impl PolicySearch {
+    /// Zero mean weights, which always stick.
+    pub fn init(settings: Settings) -> Self {
+        let dimension = settings.class.dimension();
+        Self {
+            settings,
+            mean: Vector::init(dimension),
+            std: vec![settings.sigma; dimension],
+            generation: 0,
+        }
+    }
+
+    /// The policy of the mean weights.
+    pub fn policy(&self, state: &State) -> Action {
+        self.settings.class.action(&self.mean, state)
+    }
+
+    /// Samples, scores and selects one generation, and returns its elite return.
+    pub fn next_generation<R: Rng>(&mut self, env: &Easy21Env, rng: &mut R) -> f64 {
+        let Settings {
+            method,
+            class,
+            population,
+            rollouts,
+            elite_fraction,
+            learning_rate,
+            ..
+        } = self.settings;
+
+        // The standard normal noise of each candidate, and its score.
+        let mut candidates: Vec<(Vec<f64>, f64)> = (0..population)
+            .map(|_| {
+                let noise: Vec<f64> = self.std.iter().map(|_| standard_normal(rng)).collect();
+                let weights = Vector {
+                    w: self.perturb(&noise),
+                };
+                (noise, score(env, rng, &class, &weights, rollouts))
+            })
+            .collect();
+        candidates.sort_by(|a, b| b.1.total_cmp(&a.1));
+        let elite_size =
+            ((population as f64 * elite_fraction).ceil() as usize).clamp(1, population);
+        let elite = &candidates[..elite_size];
+        let elite_return = elite.iter().map(|(_, score)| score).sum::<f64>() / elite_size as f64;
+
+        match method {
+            Method::CrossEntropy => {
+                let weights: Vec<Vec<f64>> =
+                    elite.iter().map(|(noise, _)| self.perturb(noise)).collect();
+                for (i, (mean, std)) in self.mean.w.iter_mut().zip(&mut self.std).enumerate() {
+                    let n = elite_size as f64;
+                    *mean = weights.iter().map(|w| w[i]).sum::<f64>() / n;
+                    let variance = weights.iter().map(|w| (w[i] - *mean).powi(2)).sum::<f64>() / n;
+                    *std = variance.sqrt() + EXTRA_NOISE;
+                }
+            }
+            Method::EvolutionStrategies => {
+                // Standardized scores make the step independent of the scale of the returns.
+                let n = population as f64;
+                let mean_score = candidates.iter().map(|(_, score)| score).sum::<f64>() / n;
+                let variance = candidates
+                    .iter()
+                    .map(|(_, score)| (score - mean_score).powi(2))
+                    .sum::<f64>()
+                    / n;
+                if variance > 0.0 {
+                    let sd = variance.sqrt();
+                    for (i, (mean, std)) in self.mean.w.iter_mut().zip(&self.std).enumerate() {
+                        let gradient = candidates
+                            .iter()
+                            .map(|(noise, score)| (score - mean_score) / sd * noise[i])
+                            .sum::<f64>()
+                            / (n * std);
+                        *mean += learning_rate * gradient;
+                    }
+                }
+            }
+        }
+        self.generation += 1;
+        elite_return
+    }
+
+    /// The weights at standard normal `noise` from the mean.
+    fn perturb(&self, noise: &[f64]) -> Vec<f64> {
+        self.mean
+            .w
+            .iter()
+            .zip(&self.std)
+            .zip(noise)
+            .map(|((mean, std), e)| mean + std * e)
+            .collect()
+    }
+}
+
+/// Runs a policy search for a number of generations, a few per frame, and plots the
+/// elite returns.
+pub struct Demo {
+    /// Settings of the next run.
+    pub settings: Settings,
+    /// Generations per run.
+    pub generations: usize,
+    search: Option<PolicySearch>,
+    /// Elite return of each generation of the last run.
+    curve: Vec<f64>,
+    env: Easy21Env,
+    rng: StdRng,
+}
+
+impl Default for Demo {
+    fn default() -> Self {
+        Self {
+            settings: Settings::default(),
+            generations: 50,
+            search: None,
+            curve: vec![],
+            env: Easy21Env::default(),
+            rng: StdRng::seed_from_u64(0),
+        }
+    }
+}
+
+impl Demo {
+    /// Discards the previous run and starts a new one from `seed`.
+    pub fn start(&mut self, env: Easy21Env, seed: u64) {
+        self.env = env;
+        self.rng = StdRng::seed_from_u64(seed);
+        self.search = Some(PolicySearch::init(self.settings));
+        self.curve.clear();
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.curve.len() < self.generations && self.search.is_some()
+    }
+
+    /// Searches until `budget` has passed or the run is done. Finishes at least one
+    /// generation, which may take longer than `budget`.
+    pub fn update(&mut self, budget: web_time::Duration) {
+        let Some(search) = &mut self.search else {
+            return;
+        };
+        let start_time = web_time::Instant::now();
+        while self.curve.len() < self.generations {
+            self.curve
+                .push(search.next_generation(&self.env, &mut self.rng));
+            if start_time.elapsed() >= budget {
+                break;
+            }
+        }
+    }
+
+    /// Shows the controls and the plot. Runs started from here use the given environment
+    /// and seed.
+    pub fn ui(&mut self, ui: &mut egui::Ui, env: &Easy21Env, seed: u64) {
+        let settings = &mut self.settings;
+        egui::Grid::new("policy search")
+            .num_columns(2)
+            .show(ui, |ui| {
+                ui.label("Method:");
+                ui.horizontal(|ui| {
+                    ui.selectable_value(&mut settings.method, Method::CrossEntropy, "CEM");
+                    ui.selectable_value(&mut settings.method, Method::EvolutionStrategies, "ES");
+                });
+                ui.end_row();
+
+                ui.label("Policy:");
+                ui.horizontal(|ui| {
+                    let linear = PolicyClass::Linear(Features::default());
+                    let is_linear = matches!(settings.class, PolicyClass::Linear(_));
+                    if ui.selectable_label(is_linear, "Greedy linear").clicked() && !is_linear {
+                        settings.class = linear;
+                    }
+                    ui.selectable_value(&mut settings.class, PolicyClass::Tabular, "Tabular");
+                });
+                ui.end_row();
+
+                if let PolicyClass::Linear(features) = &mut settings.class {
+                    features.ui(ui);
+                }
+
+                ui.label("Population:");
+                ui.add(egui::Slider::new(&mut settings.population, 2..=500));
+                ui.end_row();
+
+                ui.label("Episodes per candidate:");
+                ui.add(egui::Slider::new(&mut settings.rollouts, 1..=1000).logarithmic(true));
+                ui.end_row();
+
+                ui.label("Elite fraction:");
+                ui.add(egui::Slider::new(&mut settings.elite_fraction, 0.01..=1.0));
+                ui.end_row();
+
+                ui.label("σ:");
+                ui.add(egui::Slider::new(&mut settings.sigma, 0.01..=10.0).logarithmic(true));
+                ui.end_row();
+
+                if settings.method == Method::EvolutionStrategies {
+                    ui.label("Learning rate:");
+                    ui.add(
+                        egui::Slider::new(&mut settings.learning_rate, 0.01..=10.0)
+                            .logarithmic(true),
+                    );
+                    ui.end_row();
+                }
+
+                ui.label("Generations:");
+                ui.add(egui::DragValue::new(&mut self.generations).clamp_range(1..=10_000));
+                ui.end_row();
+            });
+        if ui.button("Run").clicked() {
+            self.start(*env, seed);
+        }
+        let generation = self.search.as_ref().map_or(0, |search| search.generation);
+        ui.label(format!("Generation: {} / {}", generation, self.generations));
+
+        let size = egui::vec2(ui.available_width().max(400.0), 300.0);
+        let (rect, _) = ui.allocate_exact_size(size, egui::Sense::hover());
+        let chart_ui = ui.child_ui(rect, egui::Layout::default());
+        let area = egui_plotter::EguiBackend::new(&chart_ui).into_drawing_area();
+        let (min, max) = self
+            .curve
+            .iter()
+            .fold((0.0, 0.1), |(a, b): (f64, f64), r| (a.min(*r), b.max(*r)));
+        let mut chart = ChartBuilder::on(&area)
+            .caption("Elite return", ("sans-serif", 14))
+            .margin(10)
+            .x_label_area_size(20)
+            .y_label_area_size(40)
+            .build_cartesian_2d(0.0..self.generations as f64, min..max)
+            .unwrap();
+
+        chart.configure_mesh().draw().unwrap();
+
+        let points = self
+            .curve
+            .iter()
+            .enumerate()
+            .map(|(g, r)| (g as f64 + 1.0, *r));
+        chart.draw_series(LineSeries::new(points, &BLACK)).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::easy_21::Easy21Rules;
+
+    #[test]
+    fn test_policy_search() {
+        // Without red cards the dealer rarely busts, so the initial policy, which always
+        // sticks, loses about half of its games.
+        let env = Easy21Env {
+            rules: Easy21Rules {
+                red_probability: 0.0,
+                ..Default::default()
+            },
+        };
+        let mut rng = StdRng::seed_from_u64(0);
+        let class = PolicyClass::Linear(Features::Cuboid);
+        let always_stick = score(&env, &mut rng, &class, &Vector::init(36), 10_000);
+        for method in [Method::CrossEntropy, Method::EvolutionStrategies] {
+            let mut search = PolicySearch::init(Settings {
+                method,
+                class,
+                ..Settings::default()
+            });
+            for _ in 0..20 {
+                search.next_generation(&env, &mut rng);
+            }
+            let learned = score(&env, &mut rng, &class, &search.mean, 10_000);
+            assert!(
+                learned > always_stick + 0.3,
+                "{:?}: {} {}",
+                method,
+                learned,
+                always_stick
+            );
+        }
+    }
+}
@@ -0,0 +1,108 @@
+//! Step sizes of the value updates.
+//!
+//! Tabular learners evaluate the schedule separately for every entry, at the number of
+//! earlier updates of the entry that they keep next to its value in `V` or `Q`. Linear
+//! learners have no per-entry counts and evaluate it at the number of finished episodes.
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum StepSize {
+    /// The same α for every update.
+    Constant(f64),
+    /// `1 / n` for the nth update, so that every value is the plain average of its
+    /// targets.
+    SampleAverage,
+    /// `1 / (c + n)` after n earlier updates: a sample average that starts out slower.
+    Harmonic(f64),
+    /// `n^-ω` for the nth update. Converges for ω in (0.5, 1], and forgets old targets
+    /// faster than the sample average for ω < 1.
+    Polynomial(f64),
+}
+
+impl Default for StepSize {
+    fn default() -> Self {
+        StepSize::Harmonic(10.0)
+    }
+}
+
+impl StepSize {
+    /// The step size of an update after `n` earlier updates.
+    pub fn at(&self, n: i32) -> f64 {
+        match *self {
+            StepSize::Constant(alpha) => alpha,
+            StepSize::SampleAverage => 1.0 / (n as f64 + 1.0),
+            StepSize::Harmonic(c) => 1.0 / (c + n as f64),
+            StepSize::Polynomial(omega) => (n as f64 + 1.0).powf(-omega),
+        }
+    }
+
+    /// Shows the kind of schedule and its setting as rows of a two-column grid, where
+    /// `name` tells apart the step sizes of learners with several. Returns whether
+    /// anything changed.
+    pub fn ui(&mut self, ui: &mut egui::Ui, name: &str) -> bool {
+        let mut changed = false;
+        ui.label(format!("{}:", name));
+        ui.horizontal(|ui| {
+            let kinds = [
+                ("Constant", StepSize::Constant(self.at(0))),
+                ("1 / n", StepSize::SampleAverage),
+                ("1 / (c + n)", StepSize::Harmonic(10.0)),
+                ("n^-ω", StepSize::Polynomial(0.8)),
+            ];
+            for (label, step_size) in kinds {
+                let selected = std::mem::discriminant(self) == std::mem::discriminant(&step_size);
+                if ui.selectable_label(selected, label).clicked() && !selected {
+                    *self = step_size;
+                    changed = true;
+                }
+            }
+        });
+        ui.end_row();
+
+        match self {
+            StepSize::Constant(alpha) => {
+                ui.label("α:");
+                changed |= ui
+                    .add(egui::Slider::new(alpha, 1e-6..=1.0).logarithmic(true))
+                    .changed();
+                ui.end_row();
+            }
+            StepSize::SampleAverage => {}
+            StepSize::Harmonic(c) => {
+                ui.label("α offset c:");
+                changed |= ui
+                    .add(egui::Slider::new(c, 1.0..=1000.0).logarithmic(true))
+                    .changed();
+                ui.end_row();
+            }
+            StepSize::Polynomial(omega) => {
+                ui.label("ω:");
+                changed |= ui.add(egui::Slider::new(omega, 0.5..=1.0)).changed();
+                ui.end_row();
+            }
+        }
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sample_average() {
+        let targets = [3.0, -1.0, 4.0, 1.0, -5.0];
+        for step_size in [
+            StepSize::SampleAverage,
+            StepSize::Harmonic(1.0),
+            StepSize::Polynomial(1.0),
+        ] {
+            let mut value = 10.0;
+            for (n, target) in targets.iter().enumerate() {
+                value += step_size.at(n as i32) * (target - value);
+            }
+            assert!((value - 0.4).abs() < 1e-12, "{:?}: {}", step_size, value);
+        }
+    }
+}
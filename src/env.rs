@@ -0,0 +1,87 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+/// Outcome of taking an action: the successor state and the reward collected on the way.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct Sample<S> {
+    pub state: S,
+    pub reward: f64,
+    /// Whether the episode ended. The value of `state` is zero in that case.
+    pub terminal: bool,
+}
+
+/// An episodic environment the learning algorithms can interact with.
+pub trait Environment {
+    type State: Copy;
+    type Action: Copy + PartialEq + 'static;
+
+    /// All actions. Every action is available in every state.
+    const ACTIONS: &'static [Self::Action];
+
+    /// Draws the initial state of a new episode.
+    fn reset<R: Rng>(&self, rng: &mut R) -> Self::State;
+
+    /// Takes `action` in `state`.
+    fn step<R: Rng>(
+        &self,
+        rng: &mut R,
+        state: Self::State,
+        action: Self::Action,
+    ) -> Sample<Self::State>;
+}
+
+/// An environment with finitely many non-terminal states, so that values can be stored
+/// in a table.
+pub trait Tabular: Environment {
+    /// Number of state indices.
+    const STATES: usize;
+
+    /// Index of a non-terminal state, below `STATES`.
+    fn state_index(state: &Self::State) -> usize;
+
+    /// Index of an action, below `ACTIONS.len()`.
+    fn action_index(action: &Self::Action) -> usize;
+}
+
+/// Walk right along a corridor of `LENGTH` states, starting at the left end; every step
+/// costs one. A small deterministic environment for testing the learners.
+#[cfg(test)]
+pub struct Corridor<const LENGTH: usize>;
+
+#[cfg(test)]
+impl<const LENGTH: usize> Environment for Corridor<LENGTH> {
+    type State = usize;
+    type Action = bool;
+
+    const ACTIONS: &'static [bool] = &[false, true];
+
+    fn reset<R: Rng>(&self, _rng: &mut R) -> usize {
+        0
+    }
+
+    fn step<R: Rng>(&self, _rng: &mut R, state: usize, right: bool) -> Sample<usize> {
+        let state = if right {
+            state + 1
+        } else {
+            state.saturating_sub(1)
+        };
+        Sample {
+            state,
+            reward: -1.0,
+            terminal: state == LENGTH,
+        }
+    }
+}
+
+#[cfg(test)]
+impl<const LENGTH: usize> Tabular for Corridor<LENGTH> {
+    const STATES: usize = LENGTH;
+
+    fn state_index(state: &usize) -> usize {
+        *state
+    }
+
+    fn action_index(right: &bool) -> usize {
+        *right as usize
+    }
+}
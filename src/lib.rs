@@ -3,6 +3,7 @@
 // pub mod ad;
 pub mod app;
 pub mod easy_21;
+pub mod env;
 // pub mod matrix_bench;
 // pub mod mnist;
 // pub mod net;